@@ -0,0 +1,261 @@
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+use rehype_tree_sitter_highlight::{
+  AnsiRenderer, ColorMode, HighlightEvent, Highlighter, HtmlRenderer, Theme, grammar,
+};
+use serde_json::{Value, json};
+use std::{
+  io::{self, Read, Write},
+  path::{Path, PathBuf},
+};
+
+// Used for ANSI output when no theme is given
+const DEFAULT_THEME: &str = r#"
+comment = "gray"
+keyword = "magenta"
+string = "green"
+number = "yellow"
+boolean = "yellow"
+constant = "yellow"
+function = "blue"
+type = "cyan"
+tag = "red"
+attribute = "yellow"
+operator = "light-gray"
+"#;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+  Html,
+  Ansi,
+  Json,
+  Hast,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ThemeFormat {
+  Helix,
+  Json,
+  Toml,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ColorModeArg {
+  Truecolor,
+  #[value(name = "256")]
+  Ansi256,
+  #[value(name = "16")]
+  Ansi16,
+}
+
+#[derive(Debug, Parser)]
+#[command(
+  name = "tree-sitter-highlight",
+  about = "Highlight source code with tree-sitter grammars and queries"
+)]
+struct Args {
+  /// Directories to search for grammars
+  #[arg(long = "grammar-path", required = true)]
+  grammar_paths: Vec<PathBuf>,
+
+  /// Where compiled grammars are cached. Defaults to tree-sitter's own cache directory
+  #[arg(long)]
+  cache_dir: Option<PathBuf>,
+
+  /// Read-only caches of compiled grammars, searched before `--cache-dir`
+  #[arg(long = "prebuilt-cache")]
+  prebuilt_cache_dirs: Vec<PathBuf>,
+
+  /// Fail instead of compiling grammars which aren't in a cache
+  #[arg(long)]
+  require_prebuilt: bool,
+
+  /// Load every grammar and query up front, failing if any of them can't be loaded
+  #[arg(long)]
+  strict: bool,
+
+  /// Directories of queries, taking precedence over the queries bundled with each grammar
+  #[arg(long = "query-path")]
+  query_paths: Vec<PathBuf>,
+
+  /// The language to highlight. Detected from the file extension when omitted
+  #[arg(long)]
+  lang: Option<String>,
+
+  #[arg(long, value_enum, default_value = "ansi")]
+  format: Format,
+
+  /// A theme for ANSI output and for inline styles in HTML output
+  #[arg(long)]
+  theme: Option<PathBuf>,
+
+  /// Defaults to `json` for `.json` files and `helix` otherwise
+  #[arg(long, value_enum)]
+  theme_format: Option<ThemeFormat>,
+
+  #[arg(long, value_enum, default_value = "truecolor")]
+  color_mode: ColorModeArg,
+
+  /// Files to highlight. Reads from stdin when none are given, or for `-`
+  files: Vec<PathBuf>,
+}
+
+fn load_theme(path: &Path, format: Option<ThemeFormat>) -> Result<Theme> {
+  let source = std::fs::read_to_string(path)
+    .with_context(|| format!("Failed to read theme {}", path.display()))?;
+
+  let format = format.unwrap_or(match path.extension().and_then(|ext| ext.to_str()) {
+    Some("json") => ThemeFormat::Json,
+    _ => ThemeFormat::Helix,
+  });
+
+  let theme = match format {
+    ThemeFormat::Helix => Theme::from_helix_toml(&source),
+    ThemeFormat::Json => Theme::from_json(&source),
+    ThemeFormat::Toml => Theme::from_toml(&source),
+  };
+
+  theme.with_context(|| format!("Failed to load theme {}", path.display()))
+}
+
+fn events_json(events: &[HighlightEvent]) -> Value {
+  events
+    .iter()
+    .map(|event| match event {
+      HighlightEvent::Highlight(highlight) => json!({ "type": "start", "highlight": highlight }),
+      HighlightEvent::Source { start, end } => {
+        json!({ "type": "source", "range": { "start": start, "end": end } })
+      }
+      HighlightEvent::HighlightEnd => json!({ "type": "end" }),
+    })
+    .collect()
+}
+
+// Builds the same tree as the rehype plugin: a span per range of source, classed by its innermost
+// highlight unless that is `none`, without a trailing newline
+fn hast_json(events: &[HighlightEvent], source: &[u8]) -> Value {
+  let mut children = Vec::new();
+  let mut stack: Vec<&str> = Vec::new();
+
+  for event in events {
+    match event {
+      HighlightEvent::Highlight(highlight) => stack.push(highlight),
+      HighlightEvent::HighlightEnd => {
+        stack.pop();
+      }
+      HighlightEvent::Source { start, end } => {
+        let text = String::from_utf8_lossy(&source[*start..*end]);
+        let properties = match stack.last().filter(|highlight| **highlight != "none") {
+          Some(highlight) => json!({ "className": highlight }),
+          None => json!({}),
+        };
+        children.push(json!({
+          "type": "element",
+          "tagName": "span",
+          "properties": properties,
+          "children": [{ "type": "text", "value": text }],
+        }));
+      }
+    }
+  }
+
+  if children
+    .last()
+    .is_some_and(|child| child["children"][0]["value"] == "\n")
+  {
+    children.pop();
+  }
+
+  json!({ "type": "root", "children": children })
+}
+
+fn read_input(path: Option<&Path>) -> Result<Vec<u8>> {
+  match path {
+    Some(path) if path != Path::new("-") => {
+      std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))
+    }
+    _ => {
+      let mut source = Vec::new();
+      io::stdin().read_to_end(&mut source)?;
+      Ok(source)
+    }
+  }
+}
+
+fn main() -> Result<()> {
+  let args = Args::parse();
+
+  let load_options = grammar::LoadOptions {
+    cache_dir: args.cache_dir.clone(),
+    prebuilt_cache_dirs: args.prebuilt_cache_dirs.clone(),
+    require_prebuilt: args.require_prebuilt,
+    strict: args.strict,
+  };
+  let highlighter =
+    Highlighter::from_grammar_paths(&args.grammar_paths, &args.query_paths, &load_options)?;
+
+  let theme = match &args.theme {
+    Some(path) => Some(load_theme(path, args.theme_format)?),
+    None => None,
+  };
+
+  let inputs = if args.files.is_empty() {
+    vec![None]
+  } else {
+    args.files.iter().map(|file| Some(file.as_path())).collect()
+  };
+
+  let mut stdout = io::stdout().lock();
+
+  for input in inputs {
+    let lang = match (&args.lang, input) {
+      (Some(lang), _) => lang.clone(),
+      (None, Some(path)) if path != Path::new("-") => highlighter
+        .language_for_path(path)
+        .with_context(|| {
+          format!(
+            "Failed to detect the language of {}, pass --lang",
+            path.display()
+          )
+        })?
+        .to_string(),
+      (None, _) => anyhow::bail!("--lang is required when reading from stdin"),
+    };
+
+    let source = read_input(input)?;
+
+    let events = highlighter
+      .highlight(&source, &lang)
+      .with_context(|| match input {
+        Some(path) => format!("Failed to highlight {}", path.display()),
+        None => "Failed to highlight stdin".to_string(),
+      })?;
+
+    match args.format {
+      Format::Html => {
+        let mut renderer = HtmlRenderer::new();
+        if let Some(theme) = &theme {
+          renderer = renderer.theme(theme);
+        }
+        write!(stdout, "{}", renderer.render(&events, &source))?;
+      }
+      Format::Ansi => {
+        let theme = match &theme {
+          Some(theme) => theme.clone(),
+          None => Theme::from_helix_toml(DEFAULT_THEME)?,
+        };
+        let color_mode = match args.color_mode {
+          ColorModeArg::Truecolor => ColorMode::TrueColor,
+          ColorModeArg::Ansi256 => ColorMode::Ansi256,
+          ColorModeArg::Ansi16 => ColorMode::Ansi16,
+        };
+        let renderer = AnsiRenderer::new(theme).color_mode(color_mode);
+        write!(stdout, "{}", renderer.render(&events, &source))?;
+      }
+      Format::Json => writeln!(stdout, "{}", events_json(&events))?,
+      Format::Hast => writeln!(stdout, "{}", hast_json(&events, &source))?,
+    }
+  }
+
+  Ok(())
+}
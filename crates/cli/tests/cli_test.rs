@@ -0,0 +1,194 @@
+use std::{
+  io::Write,
+  path::Path,
+  process::{Command, Stdio},
+};
+
+fn command() -> anyhow::Result<Command> {
+  let cwd = std::env::current_dir()?;
+
+  let mut command = Command::new(env!("CARGO_BIN_EXE_tree-sitter-highlight"));
+  command
+    .arg("--grammar-path")
+    .arg(cwd.join("../../fixtures/grammars/"))
+    .arg("--query-path")
+    .arg(cwd.join("../../fixtures/test-queries/injection-language"));
+  Ok(command)
+}
+
+fn run(command: &mut Command, stdin: &[u8]) -> anyhow::Result<String> {
+  let mut child = command
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+  child.stdin.take().unwrap().write_all(stdin)?;
+
+  let output = child.wait_with_output()?;
+  anyhow::ensure!(
+    output.status.success(),
+    "{}",
+    String::from_utf8_lossy(&output.stderr)
+  );
+  Ok(String::from_utf8(output.stdout)?)
+}
+
+#[test]
+fn stdin_formats() -> anyhow::Result<()> {
+  let source = b"f(1)";
+
+  let html = run(
+    command()?.args(["--lang", "js", "--format", "html"]),
+    source,
+  )?;
+  assert_eq!(html, "f(<span class=\"number\">1</span>)");
+
+  let json = run(
+    command()?.args(["--lang", "js", "--format", "json"]),
+    source,
+  )?;
+  assert_eq!(
+    serde_json::from_str::<serde_json::Value>(&json)?,
+    serde_json::json!([
+      { "type": "source", "range": { "start": 0, "end": 2 } },
+      { "type": "start", "highlight": "number" },
+      { "type": "source", "range": { "start": 2, "end": 3 } },
+      { "type": "end" },
+      { "type": "source", "range": { "start": 3, "end": 4 } },
+    ])
+  );
+
+  let hast = run(
+    command()?.args(["--lang", "js", "--format", "hast"]),
+    source,
+  )?;
+  assert_eq!(
+    serde_json::from_str::<serde_json::Value>(&hast)?,
+    serde_json::json!({
+      "type": "root",
+      "children": [
+        {
+          "type": "element",
+          "tagName": "span",
+          "properties": {},
+          "children": [{ "type": "text", "value": "f(" }],
+        },
+        {
+          "type": "element",
+          "tagName": "span",
+          "properties": { "className": "number" },
+          "children": [{ "type": "text", "value": "1" }],
+        },
+        {
+          "type": "element",
+          "tagName": "span",
+          "properties": {},
+          "children": [{ "type": "text", "value": ")" }],
+        },
+      ]
+    })
+  );
+
+  // Like the rehype plugin, a trailing newline is dropped
+  let hast = run(
+    command()?.args(["--lang", "js", "--format", "hast"]),
+    b"'a'\n",
+  )?;
+  assert_eq!(
+    serde_json::from_str::<serde_json::Value>(&hast)?,
+    serde_json::json!({
+      "type": "root",
+      "children": [
+        {
+          "type": "element",
+          "tagName": "span",
+          "properties": { "className": "string" },
+          "children": [{ "type": "text", "value": "'a'" }],
+        },
+      ]
+    })
+  );
+
+  let ansi = run(
+    command()?.args(["--lang", "js", "--format", "ansi", "--color-mode", "16"]),
+    source,
+  )?;
+  assert_eq!(ansi, "f(\x1b[33m1\x1b[0m)");
+
+  Ok(())
+}
+
+#[test]
+fn detects_language_from_extension() -> anyhow::Result<()> {
+  let dir = std::env::temp_dir().join(format!("tree-sitter-highlight-{}", std::process::id()));
+  std::fs::create_dir_all(&dir)?;
+  let file = dir.join("example.mjs");
+  std::fs::write(&file, "'a'")?;
+
+  let html = run(command()?.args(["--format", "html"]).arg(&file), b"")?;
+  assert_eq!(html, "<span class=\"string\">&#39;a&#39;</span>");
+
+  std::fs::remove_dir_all(&dir)?;
+  Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+  std::fs::create_dir_all(to)?;
+  for entry in std::fs::read_dir(from)? {
+    let entry = entry?;
+    if entry.file_type()?.is_dir() {
+      copy_dir(&entry.path(), &to.join(entry.file_name()))?;
+    } else {
+      std::fs::copy(entry.path(), to.join(entry.file_name()))?;
+    }
+  }
+  Ok(())
+}
+
+#[test]
+fn detects_language_from_file_name_only() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+  let root = std::env::temp_dir().join(format!("tree-sitter-highlight-dir-{}", std::process::id()));
+
+  // A javascript grammar whose injection regex matches anywhere in a name
+  let grammars = root.join("grammars");
+  for grammar in ["javascript", "markdown"] {
+    copy_dir(
+      &cwd.join("../../fixtures/grammars").join(grammar),
+      &grammars.join(grammar),
+    )?;
+  }
+  let config = grammars.join("javascript/tree-sitter.json");
+  let contents = std::fs::read_to_string(&config)?.replace("^(js|javascript)$", "javascript");
+  std::fs::write(&config, contents)?;
+
+  let file = root.join("javascript-notes/readme.md");
+  std::fs::create_dir_all(file.parent().unwrap())?;
+  std::fs::write(&file, "# a\n")?;
+
+  let json = run(
+    Command::new(env!("CARGO_BIN_EXE_tree-sitter-highlight"))
+      .arg("--grammar-path")
+      .arg(&grammars)
+      .args(["--format", "json"])
+      .arg(&file),
+    b"",
+  )?;
+  let events = serde_json::from_str::<serde_json::Value>(&json)?;
+  assert!(
+    events
+      .as_array()
+      .unwrap()
+      .contains(&serde_json::json!({ "type": "start", "highlight": "text.title" })),
+    "{json}"
+  );
+
+  std::fs::remove_dir_all(&root)?;
+  Ok(())
+}
+
+#[test]
+fn stdin_requires_lang() -> anyhow::Result<()> {
+  assert!(run(&mut command()?, b"1").is_err());
+  Ok(())
+}
@@ -0,0 +1,130 @@
+use crate::HighlightEvent;
+use crate::theme::{Color, Style, Theme};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+  // 24-bit colour, e.g. `\x1b[38;2;255;0;0m`
+  #[default]
+  TrueColor,
+  // The 256 colour palette, e.g. `\x1b[38;5;196m`
+  Ansi256,
+  // The basic 16 colours, e.g. `\x1b[91m`
+  Ansi16,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnsiRenderer {
+  theme: Theme,
+  color_mode: ColorMode,
+}
+
+fn push_color(codes: &mut Vec<String>, color: Color, mode: ColorMode, background: bool) {
+  let base = if background { 40 } else { 30 };
+
+  match (mode, color) {
+    (ColorMode::TrueColor, Color::Rgb(r, g, b)) => {
+      codes.push(format!("{};2;{r};{g};{b}", base + 8));
+    }
+    (ColorMode::Ansi16, _) | (_, Color::Indexed(0..16)) => {
+      let index = color.to_ansi16();
+      if index < 8 {
+        codes.push((base + index).to_string());
+      } else {
+        codes.push((base + 60 + index - 8).to_string());
+      }
+    }
+    (_, color) => {
+      codes.push(format!("{};5;{}", base + 8, color.to_ansi256()));
+    }
+  }
+}
+
+impl AnsiRenderer {
+  pub fn new(theme: Theme) -> Self {
+    Self {
+      theme,
+      color_mode: ColorMode::default(),
+    }
+  }
+
+  pub fn color_mode(mut self, color_mode: ColorMode) -> Self {
+    self.color_mode = color_mode;
+    self
+  }
+
+  fn escape_sequence(&self, style: &Style) -> Option<String> {
+    let mut codes = Vec::new();
+
+    for (enabled, code) in [
+      (style.bold, "1"),
+      (style.dim, "2"),
+      (style.italic, "3"),
+      (style.underline, "4"),
+      (style.reversed, "7"),
+      (style.strikethrough, "9"),
+    ] {
+      if enabled {
+        codes.push(code.to_string());
+      }
+    }
+
+    if let Some(fg) = style.fg {
+      push_color(&mut codes, fg, self.color_mode, false);
+    }
+    if let Some(bg) = style.bg {
+      push_color(&mut codes, bg, self.color_mode, true);
+    }
+
+    (!codes.is_empty()).then(|| format!("\x1b[{}m", codes.join(";")))
+  }
+
+  // The innermost highlight with a style in the theme is used, stopping at a `none` which resets
+  // the highlights enclosing it
+  fn style_for(&self, stack: &[&str]) -> Option<&Style> {
+    stack
+      .iter()
+      .rev()
+      .take_while(|highlight| **highlight != "none")
+      .find_map(|highlight| self.theme.get(highlight))
+  }
+
+  pub fn render(&self, events: &[HighlightEvent], source: &[u8]) -> String {
+    let mut out = String::with_capacity(source.len() * 2);
+    let mut stack: Vec<&str> = Vec::new();
+
+    for event in events {
+      match event {
+        HighlightEvent::Highlight(highlight) => stack.push(highlight),
+        HighlightEvent::HighlightEnd => {
+          stack.pop();
+        }
+        HighlightEvent::Source { start, end } => {
+          let text = String::from_utf8_lossy(&source[*start..*end]);
+          let sequence = self
+            .style_for(&stack)
+            .and_then(|style| self.escape_sequence(style));
+
+          let Some(sequence) = sequence else {
+            out.push_str(&text);
+            continue;
+          };
+
+          // Styles are reset at the end of each line so that backgrounds don't bleed into the
+          // rest of the terminal line, and so that each line of a log stands on its own.
+          for (index, line) in text.split('\n').enumerate() {
+            if index > 0 {
+              out.push('\n');
+            }
+            if !line.is_empty() {
+              out.push_str(&sequence);
+              out.push_str(line);
+              out.push_str("\x1b[0m");
+            }
+          }
+        }
+      }
+    }
+
+    out
+  }
+}
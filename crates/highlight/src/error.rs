@@ -0,0 +1,176 @@
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+use tree_sitter::{IncludedRangesError, LanguageError, Point};
+
+#[derive(Debug, thiserror::Error)]
+pub enum HighlightError {
+  #[error("Unknown language {0:?}")]
+  UnknownLanguage(String),
+  #[error(transparent)]
+  Load(LoadError),
+  #[error("Failed to load the {language} grammar: {source}")]
+  Language {
+    language: String,
+    source: LanguageError,
+  },
+  #[error("Invalid included ranges for {language}: {source}")]
+  IncludedRanges {
+    language: String,
+    source: IncludedRangesError,
+  },
+  #[error("Failed to parse {language} source")]
+  Parse { language: String },
+  #[error("Invalid #offset! in {language} pattern {pattern_index}: {reason}")]
+  InvalidOffset {
+    language: String,
+    pattern_index: usize,
+    reason: String,
+  },
+  #[error("Offset in {language} pattern {pattern_index} moves the range outside of the source")]
+  OffsetOutOfBounds {
+    language: String,
+    pattern_index: usize,
+    start: Point,
+    end: Point,
+  },
+  #[error("Injection {capture} in {language} is not valid UTF-8: {source}")]
+  Encoding {
+    language: String,
+    capture: String,
+    source: std::str::Utf8Error,
+  },
+}
+
+pub type Result<T, E = HighlightError> = std::result::Result<T, E>;
+
+// Mirrors `tree_sitter::QueryErrorKind`, with `Predicate` also covering the predicates we don't
+// know how to evaluate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryErrorKind {
+  Syntax,
+  NodeType,
+  Field,
+  Capture,
+  Predicate,
+  Structure,
+  Language,
+}
+
+impl From<&tree_sitter::QueryErrorKind> for QueryErrorKind {
+  fn from(kind: &tree_sitter::QueryErrorKind) -> Self {
+    match kind {
+      tree_sitter::QueryErrorKind::Syntax => QueryErrorKind::Syntax,
+      tree_sitter::QueryErrorKind::NodeType => QueryErrorKind::NodeType,
+      tree_sitter::QueryErrorKind::Field => QueryErrorKind::Field,
+      tree_sitter::QueryErrorKind::Capture => QueryErrorKind::Capture,
+      tree_sitter::QueryErrorKind::Predicate => QueryErrorKind::Predicate,
+      tree_sitter::QueryErrorKind::Structure => QueryErrorKind::Structure,
+      tree_sitter::QueryErrorKind::Language => QueryErrorKind::Language,
+    }
+  }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub struct QueryError {
+  pub language: String,
+  // The kind of query, e.g. `highlights.scm`
+  pub query: String,
+  // The file containing the error, which is unset only for a query without any files
+  pub path: Option<PathBuf>,
+  // Zero-based, like tree-sitter's, and relative to `path`
+  pub row: usize,
+  pub column: usize,
+  pub kind: QueryErrorKind,
+  pub message: String,
+}
+
+impl fmt::Display for QueryError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let location = match &self.path {
+      Some(path) => path.display().to_string(),
+      None => format!("{} {}", self.language, self.query),
+    };
+    write!(
+      f,
+      "{location}:{}:{}: {:?} error: {}",
+      self.row + 1,
+      self.column + 1,
+      self.kind,
+      self.message
+    )
+  }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum LoadError {
+  #[error("Failed to load the {language} grammar: {reason}")]
+  Grammar { language: String, reason: String },
+  #[error("Failed to read {} for {language}: {reason}", path.display())]
+  QueryFile {
+    language: String,
+    path: PathBuf,
+    reason: String,
+  },
+  #[error("The {query} queries for {language} inherit from themselves: {}", chain.join(" -> "))]
+  InheritanceCycle {
+    language: String,
+    query: String,
+    chain: Vec<String>,
+  },
+  #[error(transparent)]
+  Query(#[from] QueryError),
+}
+
+impl LoadError {
+  pub fn language(&self) -> &str {
+    match self {
+      LoadError::Grammar { language, .. }
+      | LoadError::QueryFile { language, .. }
+      | LoadError::InheritanceCycle { language, .. } => language,
+      LoadError::Query(err) => &err.language,
+    }
+  }
+}
+
+// Whether each language loaded, keyed by language name
+#[derive(Debug, Clone, Default)]
+pub struct LoadReport {
+  pub languages: BTreeMap<String, Result<(), LoadError>>,
+  // Patterns left out of the queries of languages which did load, see
+  // `HighlightConfiguration::warnings`
+  pub warnings: BTreeMap<String, Vec<QueryError>>,
+}
+
+impl LoadReport {
+  pub fn is_ok(&self) -> bool {
+    self.languages.values().all(Result::is_ok)
+  }
+
+  pub fn errors(&self) -> impl Iterator<Item = &LoadError> {
+    self
+      .languages
+      .values()
+      .filter_map(|result| result.as_ref().err())
+  }
+}
+
+// Lists each error on its own line, so that a failed strict load can be reported as is
+impl fmt::Display for LoadReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let errors = self.errors().collect::<Vec<_>>();
+    write!(
+      f,
+      "{} of {} languages failed to load",
+      errors.len(),
+      self.languages.len()
+    )?;
+    for err in errors {
+      write!(f, "\n  {err}")?;
+    }
+    for warning in self.warnings.values().flatten() {
+      write!(f, "\n  warning: {warning}")?;
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for LoadReport {}
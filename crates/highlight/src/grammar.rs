@@ -1,21 +1,376 @@
 use anyhow::{Context, Result};
-use std::{collections::HashMap, fs, path::PathBuf};
-use tree_sitter::Language;
+use regex::Regex;
+use std::{
+  collections::HashMap,
+  env, fs,
+  path::{Path, PathBuf},
+  sync::atomic::{AtomicUsize, Ordering},
+  thread,
+  time::Duration,
+};
+use tree_sitter::{Language, Parser};
 use tree_sitter_loader::{CompileConfig, Loader};
 
-#[derive(Debug)]
+#[cfg(feature = "wasm")]
+use std::sync::{LazyLock, Mutex};
+#[cfg(feature = "wasm")]
+use tree_sitter::{WasmStore, wasmtime::Engine};
+
+#[derive(Debug, Clone)]
 pub struct LoadedGrammar {
   pub name: String,
   pub lang: Language,
   pub injections: Vec<PathBuf>,
   pub highlights: Vec<PathBuf>,
+  pub file_types: Vec<String>,
+  pub injection_regex: Option<Regex>,
 }
 
 pub type Grammars = HashMap<String, LoadedGrammar>;
 
-pub fn load_grammars(search_paths: &[PathBuf]) -> Result<Grammars> {
+// Wasm languages can be used by any parser with a store from the same engine
+#[cfg(feature = "wasm")]
+static WASM_ENGINE: LazyLock<Engine> = LazyLock::new(Engine::default);
+#[cfg(feature = "wasm")]
+static WASM_STORE: Mutex<Option<WasmStore>> = Mutex::new(None);
+
+impl LoadedGrammar {
+  // A grammar from a compiled library, without any queries or file types
+  pub fn from_library(name: &str, path: &Path) -> Result<Self> {
+    Ok(Self::without_queries(
+      name,
+      load_language_from_library(path, name)?,
+    ))
+  }
+
+  // A grammar built with `tree-sitter build --wasm`, without any queries or file types
+  #[cfg(feature = "wasm")]
+  pub fn from_wasm(name: &str, path: &Path) -> Result<Self> {
+    Ok(Self::without_queries(
+      name,
+      load_language_from_wasm(path, name)?,
+    ))
+  }
+
+  fn without_queries(name: &str, lang: Language) -> Self {
+    LoadedGrammar {
+      name: name.to_string(),
+      lang,
+      injections: vec![],
+      highlights: vec![],
+      file_types: vec![],
+      injection_regex: None,
+    }
+  }
+}
+
+// A parser which can parse wasm languages as well as native ones
+pub(crate) fn new_parser() -> Parser {
+  #[allow(unused_mut)]
+  let mut parser = Parser::new();
+  // Without a store, setting a wasm language fails and is reported when highlighting
+  #[cfg(feature = "wasm")]
+  if let Ok(store) = WasmStore::new(&WASM_ENGINE) {
+    let _ = parser.set_wasm_store(store);
+  }
+  parser
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+  // Where compiled grammars are written to and loaded from. Defaults to tree-sitter's own parser
+  // cache, e.g. `~/.cache/tree-sitter/lib`.
+  pub cache_dir: Option<PathBuf>,
+  // Caches of compiled grammars which are only ever read from, e.g. one built ahead of time in CI.
+  // They are searched before `cache_dir`.
+  pub prebuilt_cache_dirs: Vec<PathBuf>,
+  // Fail rather than compile a grammar which isn't found in any of the caches
+  pub require_prebuilt: bool,
+  // Load every grammar and its queries when the `Highlighter` is created, failing if any of them
+  // fail to load, rather than loading each one the first time it's used
+  pub strict: bool,
+}
+
+// FNV-1a, which unlike `DefaultHasher` is stable across Rust versions so that a cache built by one
+// toolchain is valid for another
+struct ContentHash(u64);
+
+impl ContentHash {
+  fn new() -> Self {
+    Self(0xcbf29ce484222325)
+  }
+
+  fn write(&mut self, bytes: &[u8]) {
+    for byte in bytes {
+      self.0 ^= *byte as u64;
+      self.0 = self.0.wrapping_mul(0x100000001b3);
+    }
+    // Delimit each write so that moving bytes between adjacent writes changes the hash
+    self.0 ^= 0xff;
+    self.0 = self.0.wrapping_mul(0x100000001b3);
+  }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+  for entry in fs::read_dir(dir).with_context(|| format!("Failed to read directory {dir:?}"))? {
+    let path = entry?.path();
+    if path.is_dir() {
+      collect_files(&path, files)?;
+    } else {
+      files.push(path);
+    }
+  }
+  Ok(())
+}
+
+// A hash of every file in the grammar's `src` directory along with the platform the library is
+// built for, so that a cached library is rebuilt whenever the grammar changes
+fn hash_grammar_sources(src_path: &Path) -> Result<String> {
+  let mut files = Vec::new();
+  collect_files(src_path, &mut files)?;
+  files.sort();
+
+  let mut hash = ContentHash::new();
+  hash.write(env::consts::OS.as_bytes());
+  hash.write(env::consts::ARCH.as_bytes());
+  for file in files {
+    let relative = file.strip_prefix(src_path).unwrap_or(&file);
+    hash.write(relative.to_string_lossy().as_bytes());
+    hash.write(&fs::read(&file).with_context(|| format!("Failed to read {file:?}"))?);
+  }
+
+  Ok(format!("{:016x}", hash.0))
+}
+
+// The name the grammar was generated with, which names the `tree_sitter_<name>` symbol
+fn grammar_name(src_path: &Path) -> Result<String> {
+  let grammar_json = src_path.join("grammar.json");
+  let contents = fs::read_to_string(&grammar_json)
+    .with_context(|| format!("Failed to read {grammar_json:?}"))?;
+  let grammar = serde_json::from_str::<serde_json::Value>(&contents)
+    .with_context(|| format!("Failed to parse {grammar_json:?}"))?;
+
+  grammar["name"]
+    .as_str()
+    .map(String::from)
+    .with_context(|| format!("Missing name in {grammar_json:?}"))
+}
+
+// Loads the `tree_sitter_<name>` function from a compiled grammar
+pub fn load_language_from_library(path: &Path, name: &str) -> Result<Language> {
+  let symbol = format!("tree_sitter_{}", name.replace('-', "_"));
+
+  // The library must stay loaded for as long as the language is in use, which is the lifetime of
+  // the process
+  let library = unsafe { libloading::Library::new(path) }
+    .with_context(|| format!("Failed to open grammar library {path:?}"))?;
+  let language = unsafe {
+    let language_fn = library
+      .get::<unsafe extern "C" fn() -> Language>(symbol.as_bytes())
+      .with_context(|| format!("Failed to load symbol {symbol} from {path:?}"))?;
+    language_fn()
+  };
+  std::mem::forget(library);
+
+  Ok(language)
+}
+
+#[cfg(feature = "wasm")]
+pub fn load_language_from_wasm(path: &Path, name: &str) -> Result<Language> {
+  let bytes = fs::read(path).with_context(|| format!("Failed to read wasm grammar {path:?}"))?;
+
+  let mut store = WASM_STORE.lock().unwrap();
+  if store.is_none() {
+    *store = Some(WasmStore::new(&WASM_ENGINE)?);
+  }
+  let language = store
+    .as_mut()
+    .unwrap()
+    .load_language(name, &bytes)
+    .with_context(|| format!("Failed to load wasm grammar {path:?}"))?;
+
+  Ok(language)
+}
+
+#[cfg(not(feature = "wasm"))]
+pub fn load_language_from_wasm(path: &Path, _name: &str) -> Result<Language> {
+  anyhow::bail!("Loading the wasm grammar {path:?} requires the `wasm` feature")
+}
+
+// Distinguishes the temporary libraries of concurrent compiles within a process
+static NEXT_OUTPUT_ID: AtomicUsize = AtomicUsize::new(0);
+
+// How many times a failed compile is retried, and how much longer to wait before each retry
+const COMPILE_RETRIES: u32 = 3;
+const COMPILE_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+fn load_cached_language(src_path: &Path, options: &LoadOptions) -> Result<Language> {
+  let loader = Loader::new()?;
+  let name = grammar_name(src_path)?;
+  let hash = hash_grammar_sources(src_path)?;
+
+  let mut file_name = PathBuf::from(format!("{name}-{hash}"));
+  file_name.set_extension(env::consts::DLL_EXTENSION);
+
+  let cache_dir = options
+    .cache_dir
+    .clone()
+    .unwrap_or_else(|| loader.parser_lib_path.clone());
+
+  let cached = options
+    .prebuilt_cache_dirs
+    .iter()
+    .chain([&cache_dir])
+    .map(|dir| dir.join(&file_name))
+    .find(|path| path.exists());
+  if let Some(path) = cached {
+    return load_language_from_library(&path, &name);
+  }
+
+  if options.require_prebuilt {
+    anyhow::bail!("No prebuilt library {file_name:?} for grammar {name}");
+  }
+
+  fs::create_dir_all(&cache_dir)
+    .with_context(|| format!("Failed to create cache directory {cache_dir:?}"))?;
+
+  // Held while compiling so that threads and processes loading the same grammar wait for a single
+  // compile rather than each running their own
+  let lock_path = cache_dir.join(format!("{}.lock", file_name.to_string_lossy()));
+  let lock = fs::OpenOptions::new()
+    .create(true)
+    .truncate(false)
+    .write(true)
+    .open(&lock_path)
+    .with_context(|| format!("Failed to open lock file {lock_path:?}"))?;
+  lock
+    .lock()
+    .with_context(|| format!("Failed to lock {lock_path:?}"))?;
+
+  let language = compile_into_cache(&loader, src_path, &name, &cache_dir, &file_name);
+  // Anything still waiting on the removed file checks for the library once it has the lock
+  let _ = fs::remove_file(&lock_path);
+  language
+}
+
+// Compiles a grammar into the cache, unless it's already there, while holding its lock
+fn compile_into_cache(
+  loader: &Loader,
+  src_path: &Path,
+  name: &str,
+  cache_dir: &Path,
+  file_name: &Path,
+) -> Result<Language> {
+  let cached_path = cache_dir.join(file_name);
+  let mut attempts = 0;
+  loop {
+    // Compiled while another thread or process held the lock
+    if cached_path.exists() {
+      return load_language_from_library(&cached_path, name);
+    }
+
+    // Compiled under a unique temporary name and then moved into place, so that nothing ever
+    // loads a partially written library
+    let output_path = cache_dir.join(format!(
+      "{}.{}.{}.tmp",
+      file_name.to_string_lossy(),
+      std::process::id(),
+      NEXT_OUTPUT_ID.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut config = CompileConfig::new(src_path, None, Some(output_path.clone()));
+    config.name = name.to_string();
+
+    match loader.load_language_at_path_with_name(config) {
+      Ok(language) => {
+        // A library which is already in place may be loaded by another process, which on
+        // Windows means it can't be replaced
+        if cached_path.exists() {
+          let _ = fs::remove_file(&output_path);
+        } else {
+          fs::rename(&output_path, &cached_path)
+            .with_context(|| format!("Failed to write {file_name:?} to {cache_dir:?}"))?;
+        }
+        return Ok(language);
+      }
+      // The loader silently skips compiling while anything else holds its own lock for a grammar
+      // of the same name, so the library may still appear in the cache or compile on a retry
+      Err(_) if attempts < COMPILE_RETRIES => {
+        let _ = fs::remove_file(&output_path);
+        attempts += 1;
+        thread::sleep(COMPILE_RETRY_DELAY * attempts);
+      }
+      Err(err) => {
+        let _ = fs::remove_file(&output_path);
+        return Err(err.context(format!("Failed to compile grammar {name}")));
+      }
+    }
+  }
+}
+
+// A grammar directory can provide a prebuilt library or a wasm build of the grammar, which are
+// preferred over compiling its sources so that grammars can be loaded without a C compiler
+fn load_language(grammar_path: &Path, name: &str, options: &LoadOptions) -> Result<Language> {
+  let src_path = grammar_path.join("src");
+  let name = if src_path.join("grammar.json").exists() {
+    grammar_name(&src_path)?
+  } else {
+    name.to_string()
+  };
+
+  let dll = env::consts::DLL_EXTENSION;
+  let library = [
+    format!("{name}.{dll}"),
+    format!("libtree-sitter-{name}.{dll}"),
+  ]
+  .iter()
+  .map(|file_name| grammar_path.join(file_name))
+  .find(|path| path.exists());
+  if let Some(path) = library {
+    return load_language_from_library(&path, &name);
+  }
+
+  let wasm = [format!("tree-sitter-{name}.wasm"), format!("{name}.wasm")]
+    .iter()
+    .map(|file_name| grammar_path.join(file_name))
+    .find(|path| path.exists());
+  if let Some(path) = wasm {
+    return load_language_from_wasm(&path, &name);
+  }
+
+  load_cached_language(&src_path, options)
+}
+
+// A grammar found in the search paths, which isn't compiled or loaded until `load` is called
+#[derive(Debug, Clone)]
+pub struct GrammarLocation {
+  pub name: String,
+  pub path: PathBuf,
+  pub injections: Vec<PathBuf>,
+  pub highlights: Vec<PathBuf>,
+  pub file_types: Vec<String>,
+  pub injection_regex: Option<Regex>,
+}
+
+impl GrammarLocation {
+  pub fn load(&self, options: &LoadOptions) -> Result<LoadedGrammar> {
+    let language = load_language(&self.path, &self.name, options)
+      .with_context(|| format!("Failed to load language {}", self.name))?;
+
+    Ok(LoadedGrammar {
+      name: self.name.clone(),
+      lang: language,
+      injections: self.injections.clone(),
+      highlights: self.highlights.clone(),
+      file_types: self.file_types.clone(),
+      injection_regex: self.injection_regex.clone(),
+    })
+  }
+}
+
+// Finds the grammars in each of the search paths from their `tree-sitter.json`, without loading
+// any of them
+pub fn find_grammars(search_paths: &[PathBuf]) -> Result<Vec<GrammarLocation>> {
   let mut loader = Loader::new()?;
-  let mut languages = HashMap::new();
 
   for dir in search_paths {
     let entries = fs::read_dir(dir)
@@ -35,39 +390,50 @@ pub fn load_grammars(search_paths: &[PathBuf]) -> Result<Grammars> {
     }
   }
 
-  for (config, path) in loader.get_all_language_configurations() {
-    let src_path = path.join("src");
-
-    let language = loader
-      .load_language_at_path(CompileConfig::new(&src_path, None, None))
-      .with_context(|| format!("Failed to load language {}", config.language_name))?;
-
-    let injections = config
-      .injections_filenames
-      .clone()
-      .unwrap_or_default()
-      .iter()
-      .map(|path| config.root_path.join(path))
-      .collect::<Vec<_>>();
-
-    let highlights = config
-      .highlights_filenames
-      .clone()
-      .unwrap_or_default()
-      .iter()
-      .map(|path| config.root_path.join(path))
-      .collect::<Vec<_>>();
-
-    languages.insert(
-      config.language_name.clone(),
-      LoadedGrammar {
+  let locations = loader
+    .get_all_language_configurations()
+    .into_iter()
+    .map(|(config, path)| {
+      let injections = config
+        .injections_filenames
+        .clone()
+        .unwrap_or_default()
+        .iter()
+        .map(|path| config.root_path.join(path))
+        .collect::<Vec<_>>();
+
+      let highlights = config
+        .highlights_filenames
+        .clone()
+        .unwrap_or_default()
+        .iter()
+        .map(|path| config.root_path.join(path))
+        .collect::<Vec<_>>();
+
+      GrammarLocation {
         name: config.language_name.clone(),
-        lang: language,
+        path: path.to_path_buf(),
         injections,
         highlights,
-      },
-    );
-  }
+        file_types: config.file_types.clone(),
+        injection_regex: config.injection_regex.clone(),
+      }
+    })
+    .collect();
+
+  Ok(locations)
+}
+
+pub fn load_grammars(search_paths: &[PathBuf]) -> Result<Grammars> {
+  load_grammars_with_options(search_paths, &LoadOptions::default())
+}
 
-  Ok(languages)
+pub fn load_grammars_with_options(
+  search_paths: &[PathBuf],
+  options: &LoadOptions,
+) -> Result<Grammars> {
+  find_grammars(search_paths)?
+    .iter()
+    .map(|location| Ok((location.name.clone(), location.load(options)?)))
+    .collect()
 }
@@ -1,7 +1,8 @@
-use anyhow::Result;
 use std::{collections::HashMap, ops::Deref};
 use tree_sitter::{Language, Parser, Query, QueryCursor, QueryProperty, Range, StreamingIterator};
 
+use crate::error::{HighlightError, Result};
+use crate::predicates;
 use crate::ranges::{self, remap_range_for_appended_newline};
 
 #[derive(Debug, Clone)]
@@ -10,6 +11,8 @@ pub struct HighlightRegion {
   pub highlight: String,
   pub priority: u32,
   pub pattern_index: u32,
+  // How many injections deep the capture is, where captures of the highlighted language are 0
+  pub depth: u32,
 }
 
 pub fn get_priority(properties: &[QueryProperty]) -> Option<u32> {
@@ -24,18 +27,42 @@ pub fn get_priority(properties: &[QueryProperty]) -> Option<u32> {
   None
 }
 
+// Captures which control how a match is processed rather than how it is highlighted
+pub fn is_highlight_capture(name: &str) -> bool {
+  !matches!(name, "nospell" | "spell" | "conceal") && !name.starts_with('_')
+}
+
 pub fn query_highlights(
   parser: &mut Parser,
+  lang_name: &str,
   lang: &Language,
   source: &[u8],
+  included_ranges: &[Range],
   query: &Query,
+  predicates: &predicates::QueryPredicates,
 ) -> Result<Vec<HighlightRegion>> {
   let (source_with_newline, original_endpoint) = ranges::with_newline(source);
 
-  parser.set_language(lang)?;
+  parser
+    .set_language(lang)
+    .map_err(|source| HighlightError::Language {
+      language: lang_name.to_string(),
+      source,
+    })?;
+  parser
+    .set_included_ranges(&ranges::extend_ranges_for_appended_newline(
+      included_ranges,
+      &original_endpoint,
+    ))
+    .map_err(|source| HighlightError::IncludedRanges {
+      language: lang_name.to_string(),
+      source,
+    })?;
   let tree = parser
     .parse(&source_with_newline, None)
-    .ok_or_else(|| anyhow::anyhow!("Parse returned None"))?;
+    .ok_or_else(|| HighlightError::Parse {
+      language: lang_name.to_string(),
+    })?;
 
   let mut cursor = QueryCursor::new();
   let mut matches = cursor.matches(query, tree.root_node(), source_with_newline.as_ref());
@@ -53,35 +80,23 @@ pub fn query_highlights(
   let mut highlights: Vec<HighlightRegion> = Vec::new();
   while let Some(query_match) = matches.next() {
     let properties = query.property_settings(query_match.pattern_index);
-    let predicates = query.general_predicates(query_match.pattern_index);
     let priority = get_priority(properties).unwrap_or(100);
 
-    // Right now this highlighter does not support the lua-match? predicate and therefore these
-    // captures should just be completely excluded (as they only optionally match)
-    if predicates
-      .iter()
-      .any(|pred| pred.operator.deref() == "lua-match?")
-    {
+    if !predicates.satisfied(query_match, &source_with_newline) {
       continue;
     }
 
     for capture in query_match.captures {
-      if let Some(highlight_name) = capture_index.get(&capture.index) {
-        match *highlight_name {
-          "nospell" => {}
-          "spell" => {}
-          "conceal" => {}
-          value => {
-            if !value.starts_with("_") {
-              highlights.push(HighlightRegion {
-                highlight: value.to_string(),
-                range: remap_range_for_appended_newline(capture.node.range(), &original_endpoint),
-                pattern_index: query_match.pattern_index as u32,
-                priority,
-              });
-            }
-          }
-        }
+      if let Some(highlight_name) = capture_index.get(&capture.index)
+        && is_highlight_capture(highlight_name)
+      {
+        highlights.push(HighlightRegion {
+          highlight: highlight_name.to_string(),
+          range: remap_range_for_appended_newline(capture.node.range(), &original_endpoint),
+          pattern_index: query_match.pattern_index as u32,
+          depth: 0,
+          priority,
+        });
       }
     }
   }
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use crate::HighlightEvent;
+use crate::theme::{Theme, lookup_hierarchical};
+
+// How the dotted capture names are turned into class names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClassNames {
+  // `function.builtin` -> `function.builtin`, as the rehype plugin does
+  #[default]
+  Verbatim,
+  // `function.builtin` -> `function builtin`
+  DotToSpace,
+  // `function.builtin` -> `function-builtin`
+  DotToDash,
+}
+
+// Where the rules of a stylesheet apply, so that light and dark themes can be combined
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StylesheetScope {
+  Global,
+  // Wrapped in `@media (prefers-color-scheme: <scheme>)`, e.g. `dark`
+  ColorScheme(String),
+  // Nested under a class on an ancestor, e.g. `.dark .keyword`
+  Class(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HtmlRenderer {
+  class_prefix: String,
+  class_names: ClassNames,
+  highlight_mapping: HashMap<String, String>,
+  inline_styles: HashMap<String, String>,
+}
+
+pub(crate) fn escape_html(text: &str, out: &mut String) {
+  for ch in text.chars() {
+    match ch {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      '\'' => out.push_str("&#39;"),
+      ch => out.push(ch),
+    }
+  }
+}
+
+// Escapes a class name for use in a selector, e.g. `function.builtin` -> `function\.builtin`
+fn css_identifier(name: &str) -> String {
+  let mut out = String::with_capacity(name.len());
+  for (index, ch) in name.chars().enumerate() {
+    match ch {
+      '0'..='9' if index == 0 => out.push_str(&format!("\\{:x} ", ch as u32)),
+      'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => out.push(ch),
+      ch if !ch.is_ascii() => out.push(ch),
+      ch => {
+        out.push('\\');
+        out.push(ch);
+      }
+    }
+  }
+  out
+}
+
+// Source is rendered with the innermost highlight, where a `none` resets the highlights enclosing
+// it. Mirrors `highlightsSinceReset` in the rehype plugin.
+pub(crate) fn highlight_since_reset(stack: &[String]) -> Option<&str> {
+  stack
+    .last()
+    .filter(|highlight| *highlight != "none")
+    .map(String::as_str)
+}
+
+impl HtmlRenderer {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn class_prefix(mut self, prefix: &str) -> Self {
+    self.class_prefix = prefix.to_string();
+    self
+  }
+
+  pub fn class_names(mut self, class_names: ClassNames) -> Self {
+    self.class_names = class_names;
+    self
+  }
+
+  // Renames highlights before they are rendered. Mapping a highlight to `none` resets the
+  // highlights enclosing it.
+  pub fn highlight_mapping(mut self, mapping: HashMap<String, String>) -> Self {
+    self.highlight_mapping = mapping;
+    self
+  }
+
+  // CSS declarations to set in the `style` attribute of each span, keyed by capture name. Captures
+  // without an entry use the declarations of their closest parent.
+  pub fn inline_styles(mut self, styles: HashMap<String, String>) -> Self {
+    self.inline_styles = styles;
+    self
+  }
+
+  // Sets the inline styles from the theme, e.g. for HTML which is rendered without a stylesheet
+  pub fn theme(self, theme: &Theme) -> Self {
+    self.inline_styles(
+      theme
+        .styles()
+        .map(|(capture, style)| (capture.to_string(), style.to_css()))
+        .collect(),
+    )
+  }
+
+  // The class selector which matches the spans rendered for `highlight`
+  pub fn selector(&self, highlight: &str) -> String {
+    let class = |name: &str| {
+      format!(
+        ".{}",
+        css_identifier(&format!("{}{name}", self.class_prefix))
+      )
+    };
+    match self.class_names {
+      ClassNames::Verbatim => class(highlight),
+      ClassNames::DotToDash => class(&highlight.replace('.', "-")),
+      ClassNames::DotToSpace => highlight.split('.').map(class).collect(),
+    }
+  }
+
+  // A stylesheet with a rule for each capture in the theme, using the same class names as the
+  // rendered HTML
+  pub fn stylesheet(&self, theme: &Theme) -> String {
+    let mut styles = theme.styles().collect::<Vec<_>>();
+    styles.sort_by_key(|(capture, _)| *capture);
+
+    let mut out = String::new();
+    for (capture, style) in styles {
+      let declarations = style.to_css();
+      if !declarations.is_empty() {
+        out.push_str(&format!(
+          "{} {{ {declarations}; }}\n",
+          self.selector(capture)
+        ));
+      }
+    }
+    out
+  }
+
+  // A stylesheet with a rule for each of `capture_names` (see `Highlighter::capture_names`), styled
+  // by the closest capture in the theme. Unlike `stylesheet`, a capture without a rule of its own
+  // is still styled when the class names don't nest (i.e. anything but `ClassNames::DotToSpace`).
+  pub fn stylesheet_for_captures(
+    &self,
+    theme: &Theme,
+    capture_names: &[String],
+    scope: &StylesheetScope,
+  ) -> String {
+    let mut capture_names = capture_names.iter().collect::<Vec<_>>();
+    capture_names.sort();
+    capture_names.dedup();
+
+    let (selector_prefix, indent) = match scope {
+      StylesheetScope::Global => (String::new(), ""),
+      StylesheetScope::ColorScheme(_) => (String::new(), "  "),
+      StylesheetScope::Class(class) => (format!(".{} ", css_identifier(class)), ""),
+    };
+
+    let mut rules = String::new();
+    for capture in capture_names {
+      let Some(style) = theme.get(capture) else {
+        continue;
+      };
+      let declarations = style.to_css();
+      if declarations.is_empty() {
+        continue;
+      }
+
+      rules.push_str(&format!(
+        "{indent}{selector_prefix}{} {{ {declarations}; }}\n",
+        self.selector(capture)
+      ));
+    }
+
+    match scope {
+      StylesheetScope::ColorScheme(scheme) if !rules.is_empty() => {
+        format!("@media (prefers-color-scheme: {scheme}) {{\n{rules}}}\n")
+      }
+      _ => rules,
+    }
+  }
+
+  fn class_attribute(&self, highlight: &str) -> String {
+    match self.class_names {
+      ClassNames::Verbatim => format!("{}{highlight}", self.class_prefix),
+      ClassNames::DotToDash => format!("{}{}", self.class_prefix, highlight.replace('.', "-")),
+      ClassNames::DotToSpace => highlight
+        .split('.')
+        .map(|part| format!("{}{part}", self.class_prefix))
+        .collect::<Vec<_>>()
+        .join(" "),
+    }
+  }
+
+  fn render_span(&self, highlight: &str, text: &str, out: &mut String) {
+    out.push_str("<span class=\"");
+    escape_html(&self.class_attribute(highlight), out);
+    out.push('"');
+
+    if let Some(style) = lookup_hierarchical(&self.inline_styles, highlight) {
+      out.push_str(" style=\"");
+      escape_html(style, out);
+      out.push('"');
+    }
+
+    out.push('>');
+    escape_html(text, out);
+    out.push_str("</span>");
+  }
+
+  pub fn render(&self, events: &[HighlightEvent], source: &[u8]) -> String {
+    let mut out = String::with_capacity(source.len() * 2);
+    let mut stack: Vec<String> = Vec::new();
+
+    for event in events {
+      match event {
+        HighlightEvent::Highlight(highlight) => {
+          let highlight = self.highlight_mapping.get(highlight).unwrap_or(highlight);
+          stack.push(highlight.clone());
+        }
+        HighlightEvent::HighlightEnd => {
+          stack.pop();
+        }
+        HighlightEvent::Source { start, end } => {
+          let text = String::from_utf8_lossy(&source[*start..*end]);
+          if text.is_empty() {
+            continue;
+          }
+
+          match highlight_since_reset(&stack) {
+            Some(highlight) => self.render_span(highlight, &text, &mut out),
+            None => escape_html(&text, &mut out),
+          }
+        }
+      }
+    }
+
+    out
+  }
+}
@@ -1,11 +1,11 @@
-use anyhow::Result;
 use std::{collections::HashMap, ops::Deref};
 use tree_sitter::{
-  Language, Parser, Point, Query, QueryCursor, QueryPredicate, QueryPredicateArg, QueryProperty,
-  Range, StreamingIterator,
+  Language, Node, Parser, Point, Query, QueryCursor, QueryPredicate, QueryPredicateArg,
+  QueryProperty, Range, StreamingIterator,
 };
 
-use crate::ranges;
+use crate::error::{HighlightError, Result};
+use crate::{predicates, ranges};
 
 pub fn get_lang_name(properties: &[QueryProperty]) -> Option<String> {
   for property in properties {
@@ -16,6 +16,24 @@ pub fn get_lang_name(properties: &[QueryProperty]) -> Option<String> {
   None
 }
 
+fn has_property(properties: &[QueryProperty], key: &str) -> bool {
+  properties
+    .iter()
+    .any(|property| property.key.deref() == key)
+}
+
+// nvim-treesitter's markdown queries name the injected language with the
+// `set-lang-from-info-string!` directive rather than an `@injection.language` capture.
+fn info_string_capture(predicates: &[QueryPredicate]) -> Option<u32> {
+  predicates
+    .iter()
+    .filter(|pred| pred.operator.deref() == "set-lang-from-info-string!")
+    .find_map(|pred| match pred.args.first() {
+      Some(QueryPredicateArg::Capture(capture)) => Some(*capture),
+      _ => None,
+    })
+}
+
 #[derive(Debug, Clone)]
 struct RangeOffset {
   start_row: isize,
@@ -24,11 +42,7 @@ struct RangeOffset {
   end_col: isize,
 }
 
-fn parse_offset_predicate(pred: &QueryPredicate) -> Result<(u32, RangeOffset)> {
-  if pred.args.len() != 5 {
-    anyhow::bail!("Offset predicate requires 5 arguments");
-  }
-
+fn parse_offset_predicate(pred: &QueryPredicate) -> Result<(u32, RangeOffset), String> {
   let [
     QueryPredicateArg::Capture(capture),
     QueryPredicateArg::String(start_row),
@@ -37,158 +51,285 @@ fn parse_offset_predicate(pred: &QueryPredicate) -> Result<(u32, RangeOffset)> {
     QueryPredicateArg::String(end_col),
   ] = pred.args.deref()
   else {
-    anyhow::bail!("Offset predicate contained unexpected arguments");
+    return Err("expected a capture followed by 4 numbers".into());
+  };
+
+  let parse = |value: &str| {
+    value
+      .parse()
+      .map_err(|_| format!("{value:?} is not a valid offset"))
   };
 
   let range = RangeOffset {
-    start_row: start_row.parse()?,
-    start_col: start_col.parse()?,
-    end_row: end_row.parse()?,
-    end_col: end_col.parse()?,
+    start_row: parse(start_row)?,
+    start_col: parse(start_col)?,
+    end_row: parse(end_row)?,
+    end_col: parse(end_col)?,
   };
 
   Ok((*capture, range))
 }
 
-fn get_offset_modifiers(predicates: &[QueryPredicate]) -> HashMap<u32, RangeOffset> {
+fn get_offset_modifiers(
+  predicates: &[QueryPredicate],
+) -> Result<HashMap<u32, RangeOffset>, String> {
   let mut map = HashMap::new();
   for pred in predicates {
     if pred.operator.deref() != "offset!" {
       continue;
     }
 
-    let Ok((capture, range)) = parse_offset_predicate(pred) else {
-      continue;
-    };
-
+    let (capture, range) = parse_offset_predicate(pred)?;
     map.insert(capture, range);
   }
 
-  map
+  Ok(map)
 }
 
-fn point_to_byte(source: &str, point: Point) -> Option<usize> {
+// Points use byte columns, just like the points reported by tree-sitter
+fn point_to_byte(source: &[u8], point: Point) -> Option<usize> {
   let mut byte_index = 0;
+  let mut rows = 0;
 
-  for (current_row, line) in source.split_inclusive('\n').enumerate() {
-    if current_row == point.row {
-      let mut col_byte = 0;
-      for (i, ch) in line.chars().enumerate() {
-        if i == point.column {
-          break;
-        }
-        col_byte += ch.len_utf8();
-      }
-      return Some(byte_index + col_byte);
+  for line in source.split_inclusive(|byte| *byte == b'\n') {
+    if rows == point.row {
+      return (point.column <= line.len()).then_some(byte_index + point.column);
     }
 
     byte_index += line.len();
+    rows += 1;
   }
 
-  None
-}
-
-fn calculate_point_offset(value: usize, offset: isize) -> usize {
-  ((value as isize) + offset) as usize
+  (point.row == rows && point.column == 0).then_some(byte_index)
 }
 
-fn apply_offset_to_range(source: &str, range: &Range, offset: &RangeOffset) -> Range {
-  let new_start_point = Point {
-    row: calculate_point_offset(range.start_point.row, offset.start_row),
-    column: calculate_point_offset(range.start_point.column, offset.start_col),
+fn apply_offset_to_range(source: &[u8], range: &Range, offset: &RangeOffset) -> Option<Range> {
+  let start_point = Point {
+    row: range.start_point.row.checked_add_signed(offset.start_row)?,
+    column: range
+      .start_point
+      .column
+      .checked_add_signed(offset.start_col)?,
   };
-  let new_end_point = Point {
-    row: calculate_point_offset(range.end_point.row, offset.end_row),
-    column: calculate_point_offset(range.end_point.column, offset.end_col),
+  let end_point = Point {
+    row: range.end_point.row.checked_add_signed(offset.end_row)?,
+    column: range.end_point.column.checked_add_signed(offset.end_col)?,
   };
 
-  let new_start_byte = point_to_byte(source, new_start_point).unwrap();
-  let new_end_byte = point_to_byte(source, new_end_point).unwrap();
+  let start_byte = point_to_byte(source, start_point)?;
+  let end_byte = point_to_byte(source, end_point)?;
 
-  Range {
-    start_byte: new_start_byte,
-    end_byte: new_end_byte,
-    start_point: new_start_point,
-    end_point: new_end_point,
+  // Like Neovim, an offset which would invert the range (e.g. trimming the delimiters of an empty
+  // comment) is ignored rather than treated as an error.
+  if start_byte > end_byte {
+    return Some(*range);
   }
+
+  Some(Range {
+    start_byte,
+    end_byte,
+    start_point,
+    end_point,
+  })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InjectionLanguage {
+  Name(String),
+  // Resolved from the `file-types` of the loaded grammars
+  Filename(String),
+  // `injection.self`, the language of the layer containing the injection
+  Current,
+  // `injection.parent`, the language of the layer which injected the current layer
+  Parent,
 }
 
 #[derive(Debug)]
 pub struct InjectedRegion {
-  pub range: Range,
-  pub lang: String,
+  pub ranges: Vec<Range>,
+  pub lang: InjectionLanguage,
 }
 
 pub fn query_injections(
   parser: &mut Parser,
+  lang_name: &str,
   lang: &Language,
   source: &[u8],
+  included_ranges: &[Range],
   query: &Query,
+  predicates: &predicates::QueryPredicates,
 ) -> Result<Vec<InjectedRegion>> {
   let (source_with_newline, original_endpoint) = ranges::with_newline(source);
-  let source_str = String::from_utf8(Vec::from(source_with_newline.as_ref()))?;
 
-  parser.set_language(lang)?;
+  parser
+    .set_language(lang)
+    .map_err(|source| HighlightError::Language {
+      language: lang_name.to_string(),
+      source,
+    })?;
+  parser
+    .set_included_ranges(&ranges::extend_ranges_for_appended_newline(
+      included_ranges,
+      &original_endpoint,
+    ))
+    .map_err(|source| HighlightError::IncludedRanges {
+      language: lang_name.to_string(),
+      source,
+    })?;
   let tree = parser
     .parse(&source_with_newline, None)
-    .ok_or_else(|| anyhow::anyhow!("Parse returned None"))?;
+    .ok_or_else(|| HighlightError::Parse {
+      language: lang_name.to_string(),
+    })?;
 
   let mut injected_regions = Vec::new();
 
+  // Patterns marked with `injection.combined` have the content of all their matches for the same
+  // language parsed as a single document, keyed by the pattern and language like Neovim.
+  let mut combined_regions: HashMap<(usize, InjectionLanguage), Vec<Range>> = HashMap::new();
+
   let mut cursor = QueryCursor::new();
   let mut matches = cursor.matches(query, tree.root_node(), source_with_newline.as_ref());
 
   let lang_capture_index = query.capture_index_for_name("injection.language");
+  let filename_capture_index = query.capture_index_for_name("injection.filename");
   let Some(content_capture_index) = query.capture_index_for_name("injection.content") else {
     return Ok(Vec::new());
   };
 
   while let Some(query_match) = matches.next() {
-    let harcoded_lang_name = get_lang_name(query.property_settings(query_match.pattern_index));
+    if !predicates.satisfied(query_match, &source_with_newline) {
+      continue;
+    }
+
+    let properties = query.property_settings(query_match.pattern_index);
+    let harcoded_lang_name = get_lang_name(properties);
 
     let mut lang_capture = None;
-    let mut content_capture = None;
+    let mut filename_capture = None;
+    let mut content_captures = Vec::new();
     for capture in query_match.captures {
-      if let Some(lang_capture_index) = lang_capture_index {
-        if capture.index == lang_capture_index {
-          lang_capture = Some(capture);
-        }
+      if let Some(lang_capture_index) = lang_capture_index
+        && capture.index == lang_capture_index
+      {
+        lang_capture = Some(capture);
+      }
+      if let Some(filename_capture_index) = filename_capture_index
+        && capture.index == filename_capture_index
+      {
+        filename_capture = Some(capture);
       }
       if capture.index == content_capture_index {
-        content_capture = Some(capture);
+        content_captures.push(capture);
       }
     }
 
-    let Some(lang_name) = harcoded_lang_name.or_else(|| {
-      lang_capture.and_then(|capture| {
-        capture
-          .node
-          .utf8_text(&source_with_newline)
-          .ok()
-          .map(String::from)
-      })
-    }) else {
-      continue;
+    let node_text = |node: Node, capture: &str| {
+      node
+        .utf8_text(&source_with_newline)
+        .map(String::from)
+        .map_err(|source| HighlightError::Encoding {
+          language: lang_name.to_string(),
+          capture: capture.to_string(),
+          source,
+        })
     };
 
-    let Some(content_capture) = content_capture else {
+    let info_string_node = info_string_capture(query.general_predicates(query_match.pattern_index))
+      .and_then(|index| query_match.nodes_for_capture_index(index).next());
+
+    let injected_lang = if let Some(name) = harcoded_lang_name {
+      InjectionLanguage::Name(name)
+    } else if let Some(capture) = lang_capture {
+      InjectionLanguage::Name(node_text(capture.node, "@injection.language")?)
+    } else if let Some(node) = info_string_node {
+      InjectionLanguage::Name(node_text(node, "#set-lang-from-info-string!")?)
+    } else if let Some(capture) = filename_capture {
+      InjectionLanguage::Filename(node_text(capture.node, "@injection.filename")?)
+    } else if has_property(properties, "injection.self") {
+      InjectionLanguage::Current
+    } else if has_property(properties, "injection.parent") {
+      InjectionLanguage::Parent
+    } else {
       continue;
     };
 
-    let offset_modifiers =
-      get_offset_modifiers(query.general_predicates(query_match.pattern_index));
+    if content_captures.is_empty() {
+      continue;
+    }
 
-    let range = if let Some(offset) = offset_modifiers.get(&content_capture.index) {
-      apply_offset_to_range(&source_str, &content_capture.node.range(), offset)
-    } else {
-      content_capture.node.range()
-    };
+    let offset_modifiers = get_offset_modifiers(
+      query.general_predicates(query_match.pattern_index),
+    )
+    .map_err(|reason| HighlightError::InvalidOffset {
+      language: lang_name.to_string(),
+      pattern_index: query_match.pattern_index,
+      reason,
+    })?;
+
+    let include_children = has_property(properties, "injection.include-children");
+
+    let mut content_ranges = Vec::new();
+    for content_capture in content_captures {
+      let node_range = content_capture.node.range();
+      let range = match offset_modifiers.get(&content_capture.index) {
+        Some(offset) => apply_offset_to_range(&source_with_newline, &node_range, offset)
+          .ok_or_else(|| HighlightError::OffsetOutOfBounds {
+            language: lang_name.to_string(),
+            pattern_index: query_match.pattern_index,
+            start: node_range.start_point,
+            end: node_range.end_point,
+          })?,
+        None => node_range,
+      };
+
+      let ranges = if include_children {
+        vec![range]
+      } else {
+        ranges::exclude_children(&content_capture.node, range)
+      };
+
+      content_ranges.extend(
+        ranges
+          .into_iter()
+          .map(|range| ranges::remap_range_for_appended_newline(range, &original_endpoint)),
+      );
+    }
+
+    if has_property(properties, "injection.combined") {
+      combined_regions
+        .entry((query_match.pattern_index, injected_lang))
+        .or_default()
+        .extend(content_ranges);
+      continue;
+    }
+
+    let content_ranges = ranges::normalize_ranges(content_ranges);
+    if content_ranges.is_empty() {
+      continue;
+    }
 
     injected_regions.push(InjectedRegion {
-      lang: lang_name.clone(),
-      range: ranges::remap_range_for_appended_newline(range, &original_endpoint),
+      lang: injected_lang,
+      ranges: content_ranges,
     });
   }
 
+  let mut combined_regions = combined_regions
+    .into_iter()
+    .map(|((pattern_index, lang), ranges)| {
+      let region = InjectedRegion {
+        lang,
+        ranges: ranges::normalize_ranges(ranges),
+      };
+      (pattern_index, region)
+    })
+    .filter(|(_, region)| !region.ranges.is_empty())
+    .collect::<Vec<_>>();
+  combined_regions
+    .sort_by_key(|(pattern_index, region)| (*pattern_index, region.ranges[0].start_byte));
+
+  injected_regions.extend(combined_regions.into_iter().map(|(_, region)| region));
+
   Ok(injected_regions)
 }
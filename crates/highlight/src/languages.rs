@@ -0,0 +1,84 @@
+use regex::Regex;
+use std::{
+  collections::HashMap,
+  path::PathBuf,
+  sync::{Arc, OnceLock},
+};
+
+use crate::{
+  HighlightConfiguration,
+  error::{HighlightError, LoadError},
+  grammar,
+  grammar::GrammarLocation,
+  load_highlight_config,
+};
+
+// Where a registered language is loaded from the first time it is used
+struct LanguageSource {
+  grammar: GrammarLocation,
+  query_dirs: Arc<[PathBuf]>,
+  options: Arc<grammar::LoadOptions>,
+}
+
+// A language known to the highlighter. The file types and injection regex are available up front
+// for resolving language names, while the grammar and its queries are only loaded once needed.
+pub(crate) struct LanguageEntry {
+  pub(crate) file_types: Vec<String>,
+  pub(crate) injection_regex: Option<Regex>,
+  source: Option<LanguageSource>,
+  // Failures are cached too, so that a broken grammar isn't recompiled on every call
+  config: OnceLock<Result<HighlightConfiguration, LoadError>>,
+}
+
+pub(crate) type Languages = HashMap<String, LanguageEntry>;
+
+impl LanguageEntry {
+  pub(crate) fn loaded(config: HighlightConfiguration) -> Self {
+    LanguageEntry {
+      file_types: config.file_types.clone(),
+      injection_regex: config.injection_regex.clone(),
+      source: None,
+      config: OnceLock::from(Ok(config)),
+    }
+  }
+
+  pub(crate) fn registered(
+    grammar: GrammarLocation,
+    query_dirs: Arc<[PathBuf]>,
+    options: Arc<grammar::LoadOptions>,
+  ) -> Self {
+    LanguageEntry {
+      file_types: grammar.file_types.clone(),
+      injection_regex: grammar.injection_regex.clone(),
+      source: Some(LanguageSource {
+        grammar,
+        query_dirs,
+        options,
+      }),
+      config: OnceLock::new(),
+    }
+  }
+
+  // Loads the grammar and its queries on first use
+  pub(crate) fn load(&self) -> Result<&HighlightConfiguration, &LoadError> {
+    self
+      .config
+      .get_or_init(|| {
+        // Entries without a source are created with their configuration already set
+        let source = self.source.as_ref().expect("language source");
+        let grammar = source
+          .grammar
+          .load(&source.options)
+          .map_err(|err| LoadError::Grammar {
+            language: source.grammar.name.clone(),
+            reason: format!("{err:#}"),
+          })?;
+        load_highlight_config(&grammar, &source.query_dirs)
+      })
+      .as_ref()
+  }
+
+  pub(crate) fn config(&self) -> Result<&HighlightConfiguration, HighlightError> {
+    self.load().map_err(|err| HighlightError::Load(err.clone()))
+  }
+}
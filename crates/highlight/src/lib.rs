@@ -1,20 +1,61 @@
 use anyhow::Result;
 use grammar::Grammars;
-use std::{collections::HashMap, path::PathBuf};
-use tree_sitter::{Language, Parser, Query};
+use regex::Regex;
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  sync::{
+    Arc, Mutex,
+    atomic::{AtomicUsize, Ordering},
+  },
+};
+use tree_sitter::{Language, Parser, Query, Range};
 
+mod ansi;
+mod error;
 pub mod grammar;
 mod highlights;
+mod html;
 mod injections;
+mod languages;
+mod lua_pattern;
+mod offsets;
+mod overrides;
+mod predicates;
 pub mod queries;
 mod ranges;
+mod resolver;
+mod theme;
+mod vim_regex;
+
+pub use ansi::{AnsiRenderer, ColorMode};
+pub use error::{HighlightError, LoadError, LoadReport, QueryError, QueryErrorKind};
+pub use html::{ClassNames, HtmlRenderer, StylesheetScope};
+pub use lua_pattern::{LuaPattern, LuaPatternError};
+pub use offsets::{OffsetUnit, convert_offsets};
+pub use resolver::LanguageResolver;
+pub use theme::{Color, Style, Theme, ThemeError};
+pub use vim_regex::{VimRegex, VimRegexError};
 
 use crate::highlights::HighlightRegion;
+use crate::languages::{LanguageEntry, Languages};
+use crate::overrides::{OverrideCache, QueryOverrides};
 
 pub struct HighlightConfiguration {
   pub language: Language,
   pub injections: Query,
   pub highlights: Query,
+  pub file_types: Vec<String>,
+  pub injection_regex: Option<Regex>,
+  // Patterns which were left out of the queries because they use predicates we can't evaluate
+  pub warnings: Vec<QueryError>,
+  injection_predicates: predicates::QueryPredicates,
+  highlight_predicates: predicates::QueryPredicates,
+  // What the queries were loaded from, so that they can be loaded again with per-call overrides
+  grammar: grammar::LoadedGrammar,
+  query_dirs: Vec<PathBuf>,
+  injections_source: String,
+  highlights_source: String,
 }
 
 type Configurations = HashMap<String, HighlightConfiguration>;
@@ -22,93 +63,355 @@ type Configurations = HashMap<String, HighlightConfiguration>;
 pub fn load_highlight_config(
   grammar: &grammar::LoadedGrammar,
   queries_dirs: &[PathBuf],
-) -> Result<HighlightConfiguration> {
-  let config = HighlightConfiguration {
-    language: grammar.lang.clone(),
-    injections: queries::load_query(grammar, &grammar.injections, queries_dirs, "injections.scm")
-      .map_err(|err| anyhow::format_err!("{err:?}"))?,
-    highlights: queries::load_query(grammar, &grammar.highlights, queries_dirs, "highlights.scm")
-      .map_err(|err| anyhow::format_err!("{err:?}"))?,
-  };
+) -> Result<HighlightConfiguration, LoadError> {
+  let injections =
+    queries::read_query_source(grammar, &grammar.injections, queries_dirs, "injections.scm")?;
+  let highlights =
+    queries::read_query_source(grammar, &grammar.highlights, queries_dirs, "highlights.scm")?;
 
-  Ok(config)
+  HighlightConfiguration::compile(grammar, queries_dirs, &injections, &highlights)
 }
 
 impl HighlightConfiguration {
-  pub fn from_query_paths(grammars: &Grammars, query_dirs: &[PathBuf]) -> Configurations {
-    grammars
+  fn compile(
+    grammar: &grammar::LoadedGrammar,
+    queries_dirs: &[PathBuf],
+    injections_source: &queries::QuerySource,
+    highlights_source: &queries::QuerySource,
+  ) -> Result<Self, LoadError> {
+    let injections = queries::compile_query(grammar, injections_source, "injections.scm")?;
+    let highlights = queries::compile_query(grammar, highlights_source, "highlights.scm")?;
+
+    Ok(HighlightConfiguration {
+      language: grammar.lang.clone(),
+      injections: injections.query,
+      highlights: highlights.query,
+      file_types: grammar.file_types.clone(),
+      injection_regex: grammar.injection_regex.clone(),
+      warnings: [injections.warnings, highlights.warnings].concat(),
+      injection_predicates: injections.predicates,
+      highlight_predicates: highlights.predicates,
+      grammar: grammar.clone(),
+      query_dirs: queries_dirs.to_vec(),
+      injections_source: injections_source.contents.clone(),
+      highlights_source: highlights_source.contents.clone(),
+    })
+  }
+
+  // The names of the highlights this configuration's queries can produce
+  pub fn capture_names(&self) -> impl Iterator<Item = &str> {
+    self
+      .highlights
+      .capture_names()
       .iter()
-      .filter_map(
-        |(lang, grammar)| match load_highlight_config(grammar, query_dirs) {
-          Ok(config) => Some((lang.clone(), config)),
-          Err(err) => {
-            eprintln!("Failed to load grammar {lang}: {err:?}");
-            None
-          }
-        },
-      )
-      .collect()
+      .copied()
+      .filter(|name| highlights::is_highlight_capture(name))
+  }
+
+  // Languages whose queries fail to load are left out, see `from_query_paths_with_report`
+  pub fn from_query_paths(grammars: &Grammars, query_dirs: &[PathBuf]) -> Configurations {
+    Self::from_query_paths_with_report(grammars, query_dirs).0
+  }
+
+  pub fn from_query_paths_with_report(
+    grammars: &Grammars,
+    query_dirs: &[PathBuf],
+  ) -> (Configurations, LoadReport) {
+    let mut configurations = Configurations::new();
+    let mut report = LoadReport::default();
+
+    for (lang, grammar) in grammars {
+      let result = load_highlight_config(grammar, query_dirs).map(|config| {
+        if !config.warnings.is_empty() {
+          report
+            .warnings
+            .insert(lang.clone(), config.warnings.clone());
+        }
+        configurations.insert(lang.clone(), config);
+      });
+      report.languages.insert(lang.clone(), result);
+    }
+
+    (configurations, report)
   }
 }
 
+// The languages and their compiled queries are shared by every clone of a highlighter, which can
+// all be used from any number of threads at once
+#[derive(Clone)]
 pub struct Highlighter {
-  configurations: Configurations,
-  parser: Parser,
+  languages: Arc<Languages>,
+  resolver: Arc<LanguageResolver>,
+  parsers: Arc<ParserPool>,
+  overrides: Arc<OverrideCache>,
+  // The number of threads used by `highlight_many`
+  workers: usize,
+}
+
+fn default_workers() -> usize {
+  std::thread::available_parallelism().map_or(1, usize::from)
+}
+
+// Parsers are reused between calls, with as many parsers as there have been concurrent calls
+#[derive(Default)]
+struct ParserPool {
+  parsers: Mutex<Vec<Parser>>,
+}
+
+impl ParserPool {
+  fn take(&self) -> Parser {
+    self
+      .parsers
+      .lock()
+      .unwrap()
+      .pop()
+      .unwrap_or_else(grammar::new_parser)
+  }
+
+  fn put(&self, parser: Parser) {
+    self.parsers.lock().unwrap().push(parser);
+  }
+}
+
+fn loaded_languages(configurations: Configurations) -> Languages {
+  configurations
+    .into_iter()
+    .map(|(lang, config)| (lang, LanguageEntry::loaded(config)))
+    .collect()
 }
 
 impl Highlighter {
   pub fn new(configurations: Configurations) -> Self {
+    Self::with_resolver(configurations, LanguageResolver::new())
+  }
+
+  // Registers the grammars found in the search paths without loading them. Each grammar and its
+  // queries are loaded the first time the language is highlighted, including as an injection.
+  pub fn from_grammar_paths(
+    search_paths: &[PathBuf],
+    query_dirs: &[PathBuf],
+    options: &grammar::LoadOptions,
+  ) -> Result<Self> {
+    let query_dirs: Arc<[PathBuf]> = query_dirs.into();
+    let options = Arc::new(options.clone());
+
+    let languages = grammar::find_grammars(search_paths)?
+      .into_iter()
+      .map(|location| {
+        (
+          location.name.clone(),
+          LanguageEntry::registered(location, query_dirs.clone(), options.clone()),
+        )
+      })
+      .collect();
+
+    let highlighter = Highlighter {
+      languages: Arc::new(languages),
+      resolver: Arc::default(),
+      parsers: Arc::default(),
+      overrides: Arc::default(),
+      workers: default_workers(),
+    };
+
+    if options.strict {
+      let report = highlighter.load_all();
+      if !report.is_ok() {
+        return Err(report.into());
+      }
+    }
+
+    Ok(highlighter)
+  }
+
+  // Loads every language which hasn't been loaded yet, reporting whether each one loaded
+  pub fn load_all(&self) -> LoadReport {
+    let mut report = LoadReport::default();
+    for (lang, language) in self.languages.iter() {
+      let result = language.load().map_err(Clone::clone).map(|config| {
+        if !config.warnings.is_empty() {
+          report
+            .warnings
+            .insert(lang.clone(), config.warnings.clone());
+        }
+      });
+      report.languages.insert(lang.clone(), result);
+    }
+
+    report
+  }
+
+  // Every highlight name which can be produced by the registered languages, sorted and
+  // deduplicated. This loads every language which hasn't been loaded yet, skipping any which fail
+  // to load.
+  pub fn capture_names(&self) -> Vec<String> {
+    let names = self
+      .languages
+      .values()
+      .filter_map(|language| language.load().ok())
+      .flat_map(|config| config.capture_names())
+      .collect::<std::collections::BTreeSet<_>>();
+    names.into_iter().map(String::from).collect()
+  }
+
+  // The language of a file, detected from its file name or extension through the `file-types` of
+  // each grammar. The rest of the path is ignored.
+  pub fn language_for_path(&self, path: &Path) -> Option<&str> {
+    let file_name = path.file_name()?.to_str()?;
+    self.resolver.resolve_filename(&self.languages, file_name)
+  }
+
+  pub fn with_resolver(configurations: Configurations, resolver: LanguageResolver) -> Self {
     Highlighter {
-      parser: Parser::new(),
-      configurations,
+      languages: Arc::new(loaded_languages(configurations)),
+      resolver: Arc::new(resolver),
+      parsers: Arc::default(),
+      overrides: Arc::default(),
+      workers: default_workers(),
     }
   }
 }
 
-fn query_highlights<'a>(
-  parser: &'a mut Parser,
+// Injections nested deeper than this are left unhighlighted, so that queries which keep injecting
+// into their own content can't recurse without end
+const MAX_INJECTION_DEPTH: u32 = 16;
+
+// Whether an injection covers exactly the same source as the layer it is in
+fn is_same_layer(source: &[u8], included_ranges: &[Range], ranges: &[Range]) -> bool {
+  let bytes = |range: &Range| range.start_byte..range.end_byte;
+  if included_ranges.is_empty() {
+    return matches!(ranges, [range] if bytes(range) == (0..source.len()));
+  }
+  included_ranges
+    .iter()
+    .map(bytes)
+    .eq(ranges.iter().map(bytes))
+}
+
+// How the languages of every layer of a single call are resolved and loaded
+struct HighlightContext<'a> {
+  languages: &'a Languages,
+  resolver: &'a LanguageResolver,
+  overrides: Option<&'a QueryOverrides<'a>>,
+}
+
+fn query_highlights(
+  parser: &mut Parser,
+  context: &HighlightContext,
   lang: &str,
-  configurations: &'a Configurations,
+  parent_lang: Option<&str>,
   source: &[u8],
-  layer: u32,
-) -> Vec<highlights::HighlightRegion> {
-  let Some(config) = configurations.get(lang) else {
-    return Vec::new();
+  included_ranges: &[Range],
+  depth: u32,
+) -> Result<Vec<highlights::HighlightRegion>, HighlightError> {
+  let HighlightContext {
+    languages,
+    resolver,
+    overrides,
+  } = context;
+  let Some(lang) = resolver.resolve(languages, lang) else {
+    return Err(HighlightError::UnknownLanguage(lang.to_string()));
+  };
+  let base_config = languages[lang].config()?;
+  let overridden;
+  let config = match overrides {
+    Some(overrides) => {
+      overridden = overrides
+        .config(base_config)
+        .map_err(HighlightError::Load)?;
+      overridden.as_deref().unwrap_or(base_config)
+    }
+    None => base_config,
   };
 
-  let injections =
-    injections::query_injections(parser, &config.language, source, &config.injections)
-      .expect("Failed to query injections");
-  let mut highlights =
-    highlights::query_highlights(parser, &config.language, source, &config.highlights)
-      .expect("Failed to query highlights");
+  let injections = injections::query_injections(
+    parser,
+    lang,
+    &config.language,
+    source,
+    included_ranges,
+    &config.injections,
+    &config.injection_predicates,
+  )?;
+  let mut highlights = highlights::query_highlights(
+    parser,
+    lang,
+    &config.language,
+    source,
+    included_ranges,
+    &config.highlights,
+    &config.highlight_predicates,
+  )?;
+
+  if depth >= MAX_INJECTION_DEPTH {
+    return Ok(highlights);
+  }
 
   for region in injections {
+    let injected_lang = match &region.lang {
+      injections::InjectionLanguage::Name(name) => Some(name.as_str()),
+      injections::InjectionLanguage::Filename(filename) => {
+        resolver.resolve_filename(languages, filename)
+      }
+      injections::InjectionLanguage::Current => Some(lang),
+      injections::InjectionLanguage::Parent => parent_lang,
+    };
+    // Injections of languages which are unknown or fail to load are left unhighlighted
+    let Some(injected_lang) = injected_lang.and_then(|name| resolver.resolve(languages, name))
+    else {
+      continue;
+    };
+    if languages[injected_lang].load().is_err() {
+      continue;
+    }
+    // Injecting a language into its own layer would only produce the same layer again
+    if injected_lang == lang && is_same_layer(source, included_ranges, &region.ranges) {
+      continue;
+    }
+
+    let Some(outer_range) = ranges::enclosing_range(&region.ranges) else {
+      continue;
+    };
+
+    // The injected layer is parsed from the slice of source spanning all of its ranges, with
+    // only the ranges themselves included in the parse.
+    let relative_ranges = region
+      .ranges
+      .iter()
+      .map(|range| ranges::relative_range(&outer_range, range))
+      .collect::<Vec<_>>();
+
     let injection_highlights = query_highlights(
       parser,
-      &region.lang,
-      configurations,
-      &source[region.range.start_byte..region.range.end_byte],
-      layer * 10,
-    )
+      context,
+      injected_lang,
+      Some(lang),
+      &source[outer_range.start_byte..outer_range.end_byte],
+      &relative_ranges,
+      depth + 1,
+    )?
     .iter()
-    .map(|highlight| highlights::HighlightRegion {
-      highlight: highlight.highlight.clone(),
-      priority: highlight.priority,
-      pattern_index: layer * highlight.pattern_index,
-      range: ranges::remap_injected_region_highlight_range(&region.range, &highlight.range),
+    .flat_map(|highlight| {
+      let range = ranges::remap_injected_region_highlight_range(&outer_range, &highlight.range);
+      ranges::clip_to_ranges(&range, &region.ranges)
+        .into_iter()
+        .map(|range| highlights::HighlightRegion {
+          highlight: highlight.highlight.clone(),
+          priority: highlight.priority,
+          pattern_index: highlight.pattern_index,
+          depth: highlight.depth + 1,
+          range,
+        })
     })
     .collect::<Vec<_>>();
-
     for highlight in injection_highlights {
       highlights.push(highlight)
     }
   }
 
-  highlights
+  Ok(highlights)
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum HighlightEvent {
   Highlight(String),
   Source { start: usize, end: usize },
@@ -116,15 +419,103 @@ pub enum HighlightEvent {
 }
 
 impl Highlighter {
-  pub fn highlight(&mut self, source: &[u8], lang: &str) -> Vec<HighlightEvent> {
-    let mut highlights = query_highlights(&mut self.parser, lang, &self.configurations, source, 1);
+  pub fn highlight(
+    &self,
+    source: &[u8],
+    lang: &str,
+  ) -> Result<Vec<HighlightEvent>, HighlightError> {
+    self.highlight_with_queries(source, lang, &[])
+  }
+
+  // Sets the number of threads used by `highlight_many`, which defaults to the available
+  // parallelism
+  pub fn workers(mut self, workers: usize) -> Self {
+    self.workers = workers.max(1);
+    self
+  }
+
+  // Highlights each of `(source, lang)` in parallel, returning the results in the same order
+  pub fn highlight_many(
+    &self,
+    inputs: &[(&[u8], &str)],
+  ) -> Vec<Result<Vec<HighlightEvent>, HighlightError>> {
+    let workers = self.workers.min(inputs.len());
+    if workers <= 1 {
+      return inputs
+        .iter()
+        .map(|(source, lang)| self.highlight(source, lang))
+        .collect();
+    }
+
+    // Each worker takes the next input until there are none left
+    let next = AtomicUsize::new(0);
+    let mut results = std::thread::scope(|scope| {
+      let workers = (0..workers)
+        .map(|_| {
+          scope.spawn(|| {
+            let mut results = Vec::new();
+            loop {
+              let index = next.fetch_add(1, Ordering::Relaxed);
+              let Some((source, lang)) = inputs.get(index) else {
+                break;
+              };
+              results.push((index, self.highlight(source, lang)));
+            }
+            results
+          })
+        })
+        .collect::<Vec<_>>();
+
+      workers
+        .into_iter()
+        .flat_map(|worker| worker.join().unwrap())
+        .collect::<Vec<_>>()
+    });
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+  }
+
+  // The number of configurations compiled for the query directories passed to
+  // `highlight_with_queries` which are kept for later calls
+  pub fn cached_query_overrides(&self) -> usize {
+    self.overrides.len()
+  }
+
+  // Highlights with extra query directories on top of the highlighter's own, for this call only.
+  // They apply to injected languages too, and each distinct set of queries is compiled once and
+  // then reused by later calls.
+  pub fn highlight_with_queries(
+    &self,
+    source: &[u8],
+    lang: &str,
+    query_dirs: &[PathBuf],
+  ) -> Result<Vec<HighlightEvent>, HighlightError> {
+    let overrides = QueryOverrides::new(query_dirs, &self.overrides);
+
+    let mut parser = self.parsers.take();
+    let context = HighlightContext {
+      languages: &self.languages,
+      resolver: &self.resolver,
+      overrides: (!query_dirs.is_empty()).then_some(&overrides),
+    };
+    let highlights = query_highlights(&mut parser, &context, lang, None, source, &[], 0);
+    self.parsers.put(parser);
+    let mut highlights = highlights?;
     highlights.sort_by(|a, b| {
       let start_pos = a.range.start_byte.cmp(&b.range.start_byte);
       match start_pos {
         std::cmp::Ordering::Equal => {
           let end_pos = b.range.end_byte.cmp(&a.range.end_byte);
           match end_pos {
-            std::cmp::Ordering::Equal => a.pattern_index.cmp(&b.pattern_index),
+            // Captures on the same range are ordered by priority so that the highest priority
+            // capture ends up innermost and wins, then injected captures over those of the layers
+            // they are injected into, falling back to pattern order like Neovim.
+            std::cmp::Ordering::Equal => a
+              .priority
+              .cmp(&b.priority)
+              .then(a.depth.cmp(&b.depth))
+              .then(a.pattern_index.cmp(&b.pattern_index)),
             ordering => ordering,
           }
         }
@@ -160,6 +551,16 @@ impl Highlighter {
           continue;
         }
 
+        // A capture which is fully covered by an enclosing capture of a higher priority would
+        // never be visible, so it is dropped rather than rendered on top of it.
+        let shadowed = stack.iter().any(|region| {
+          region.priority > current.priority && region.range.end_byte >= current.range.end_byte
+        });
+        if shadowed {
+          index += 1;
+          continue;
+        }
+
         if previous_start < current_start && byte < current_start {
           events.push(HighlightEvent::Source {
             start: byte,
@@ -184,7 +585,7 @@ impl Highlighter {
     stack.reverse();
 
     for highlight in stack {
-      if byte <= highlight.range.end_byte {
+      if byte < highlight.range.end_byte {
         events.push(HighlightEvent::Source {
           start: byte,
           end: highlight.range.end_byte,
@@ -194,13 +595,13 @@ impl Highlighter {
       events.push(HighlightEvent::HighlightEnd);
     }
 
-    if byte < source.len() - 1 {
+    if byte < source.len() {
       events.push(HighlightEvent::Source {
         start: byte,
-        end: source.len() - 1,
+        end: source.len(),
       });
     }
 
-    events
+    Ok(events)
   }
 }
@@ -0,0 +1,403 @@
+// A port of the Lua pattern matcher from `lstrlib.c`, used to evaluate the `lua-match?` family of
+// predicates found in nvim-treesitter queries. Patterns operate on raw bytes and character classes
+// follow the C locale, exactly as they do in Neovim.
+
+const ESCAPE: u8 = b'%';
+const MAX_CAPTURES: usize = 32;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum LuaPatternError {
+  #[error("malformed pattern (ends with '%')")]
+  TrailingEscape,
+  #[error("malformed pattern (missing ']')")]
+  MissingBracket,
+  #[error("missing arguments to '%b'")]
+  MissingBalanceArguments,
+  #[error("missing '[' after '%f' in pattern")]
+  MissingFrontierSet,
+  #[error("invalid pattern capture")]
+  InvalidCapture,
+  #[error("unfinished capture")]
+  UnfinishedCapture,
+  #[error("too many captures")]
+  TooManyCaptures,
+}
+
+#[derive(Debug, Clone)]
+pub struct LuaPattern {
+  pattern: Vec<u8>,
+}
+
+impl LuaPattern {
+  pub fn new(pattern: &str) -> Result<Self, LuaPatternError> {
+    let pattern = pattern.as_bytes().to_vec();
+    validate(&pattern)?;
+    Ok(Self { pattern })
+  }
+
+  // Equivalent to `string.find(subject, pattern) ~= nil`
+  pub fn is_match(&self, subject: &[u8]) -> bool {
+    let (anchored, start) = match self.pattern.first() {
+      Some(b'^') => (true, 1),
+      _ => (false, 0),
+    };
+
+    let mut state = MatchState {
+      src: subject,
+      pat: &self.pattern,
+      level: 0,
+      captures: [(0, CaptureLen::Unclosed); MAX_CAPTURES],
+    };
+
+    let mut s = 0;
+    loop {
+      state.level = 0;
+      if state.do_match(s, start).is_some() {
+        return true;
+      }
+      s += 1;
+      if anchored || s > subject.len() {
+        return false;
+      }
+    }
+  }
+}
+
+fn class_end(pat: &[u8], mut p: usize) -> Result<usize, LuaPatternError> {
+  let c = pat[p];
+  p += 1;
+
+  match c {
+    ESCAPE => {
+      if p >= pat.len() {
+        return Err(LuaPatternError::TrailingEscape);
+      }
+      Ok(p + 1)
+    }
+    b'[' => {
+      if pat.get(p) == Some(&b'^') {
+        p += 1;
+      }
+      // The first character of a set is always part of the set, which allows `[]]`
+      loop {
+        if p >= pat.len() {
+          return Err(LuaPatternError::MissingBracket);
+        }
+        let c = pat[p];
+        p += 1;
+        if c == ESCAPE && p < pat.len() {
+          p += 1;
+        }
+        match pat.get(p) {
+          Some(b']') => return Ok(p + 1),
+          Some(_) => {}
+          None => return Err(LuaPatternError::MissingBracket),
+        }
+      }
+    }
+    _ => Ok(p),
+  }
+}
+
+fn validate(pat: &[u8]) -> Result<(), LuaPatternError> {
+  let mut p = if pat.first() == Some(&b'^') { 1 } else { 0 };
+  let mut open = 0;
+  let mut captures = 0;
+
+  while p < pat.len() {
+    match pat[p] {
+      b'(' => {
+        open += 1;
+        captures += 1;
+        if captures > MAX_CAPTURES {
+          return Err(LuaPatternError::TooManyCaptures);
+        }
+        p += 1;
+      }
+      b')' => {
+        if open == 0 {
+          return Err(LuaPatternError::InvalidCapture);
+        }
+        open -= 1;
+        p += 1;
+      }
+      ESCAPE => match pat.get(p + 1) {
+        None => return Err(LuaPatternError::TrailingEscape),
+        Some(b'b') => {
+          if p + 3 >= pat.len() {
+            return Err(LuaPatternError::MissingBalanceArguments);
+          }
+          p += 4;
+        }
+        Some(b'f') => {
+          p += 2;
+          if pat.get(p) != Some(&b'[') {
+            return Err(LuaPatternError::MissingFrontierSet);
+          }
+          p = class_end(pat, p)?;
+        }
+        Some(_) => p += 2,
+      },
+      _ => p = class_end(pat, p)?,
+    }
+  }
+
+  if open > 0 {
+    return Err(LuaPatternError::UnfinishedCapture);
+  }
+
+  Ok(())
+}
+
+fn match_class(c: u8, class: u8) -> bool {
+  let matches = match class.to_ascii_lowercase() {
+    b'a' => c.is_ascii_alphabetic(),
+    b'c' => c.is_ascii_control(),
+    b'd' => c.is_ascii_digit(),
+    b'g' => c.is_ascii_graphic(),
+    b'l' => c.is_ascii_lowercase(),
+    b'p' => c.is_ascii_punctuation(),
+    // C `isspace` also accepts the vertical tab, which `u8::is_ascii_whitespace` does not
+    b's' => c.is_ascii_whitespace() || c == 0x0b,
+    b'u' => c.is_ascii_uppercase(),
+    b'w' => c.is_ascii_alphanumeric(),
+    b'x' => c.is_ascii_hexdigit(),
+    b'z' => c == 0,
+    _ => return class == c,
+  };
+
+  if class.is_ascii_uppercase() {
+    !matches
+  } else {
+    matches
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CaptureLen {
+  Position,
+  Unclosed,
+  Closed(usize),
+}
+
+struct MatchState<'a> {
+  src: &'a [u8],
+  pat: &'a [u8],
+  level: usize,
+  captures: [(usize, CaptureLen); MAX_CAPTURES],
+}
+
+impl MatchState<'_> {
+  // `p` points at the opening `[` and `ec` at the closing `]` of the set
+  fn match_bracket_class(&self, c: u8, mut p: usize, ec: usize) -> bool {
+    let mut sig = true;
+    if self.pat[p + 1] == b'^' {
+      sig = false;
+      p += 1;
+    }
+
+    p += 1;
+    while p < ec {
+      if self.pat[p] == ESCAPE {
+        p += 1;
+        if match_class(c, self.pat[p]) {
+          return sig;
+        }
+      } else if self.pat[p + 1] == b'-' && p + 2 < ec {
+        if self.pat[p] <= c && c <= self.pat[p + 2] {
+          return sig;
+        }
+        p += 2;
+      } else if self.pat[p] == c {
+        return sig;
+      }
+      p += 1;
+    }
+
+    !sig
+  }
+
+  fn single_match(&self, s: usize, p: usize, ep: usize) -> bool {
+    let Some(&c) = self.src.get(s) else {
+      return false;
+    };
+
+    match self.pat[p] {
+      b'.' => true,
+      ESCAPE => match_class(c, self.pat[p + 1]),
+      b'[' => self.match_bracket_class(c, p, ep - 1),
+      literal => literal == c,
+    }
+  }
+
+  fn do_match(&mut self, mut s: usize, mut p: usize) -> Option<usize> {
+    loop {
+      if p == self.pat.len() {
+        return Some(s);
+      }
+
+      match self.pat[p] {
+        b'(' => {
+          return if self.pat.get(p + 1) == Some(&b')') {
+            self.start_capture(s, p + 2, CaptureLen::Position)
+          } else {
+            self.start_capture(s, p + 1, CaptureLen::Unclosed)
+          };
+        }
+        b')' => return self.end_capture(s, p + 1),
+        b'$' if p + 1 == self.pat.len() => {
+          return (s == self.src.len()).then_some(s);
+        }
+        ESCAPE if self.pat[p + 1] == b'b' => {
+          s = self.match_balance(s, p + 2)?;
+          p += 4;
+          continue;
+        }
+        ESCAPE if self.pat[p + 1] == b'f' => {
+          p += 2;
+          let ep = class_end(self.pat, p).ok()?;
+          let previous = if s == 0 { 0 } else { self.src[s - 1] };
+          let current = self.src.get(s).copied().unwrap_or(0);
+          if !self.match_bracket_class(previous, p, ep - 1)
+            && self.match_bracket_class(current, p, ep - 1)
+          {
+            p = ep;
+            continue;
+          }
+          return None;
+        }
+        ESCAPE if self.pat[p + 1].is_ascii_digit() => {
+          s = self.match_capture(s, self.pat[p + 1])?;
+          p += 2;
+          continue;
+        }
+        _ => {}
+      }
+
+      let ep = class_end(self.pat, p).ok()?;
+      let matched = self.single_match(s, p, ep);
+
+      match self.pat.get(ep) {
+        Some(b'?') => {
+          if matched && let Some(end) = self.do_match(s + 1, ep + 1) {
+            return Some(end);
+          }
+          p = ep + 1;
+        }
+        Some(b'*') => return self.max_expand(s, p, ep),
+        Some(b'+') => {
+          return if matched {
+            self.max_expand(s + 1, p, ep)
+          } else {
+            None
+          };
+        }
+        Some(b'-') => return self.min_expand(s, p, ep),
+        _ => {
+          if !matched {
+            return None;
+          }
+          s += 1;
+          p = ep;
+        }
+      }
+    }
+  }
+
+  fn max_expand(&mut self, s: usize, p: usize, ep: usize) -> Option<usize> {
+    let mut count = 0;
+    while self.single_match(s + count, p, ep) {
+      count += 1;
+    }
+
+    loop {
+      if let Some(end) = self.do_match(s + count, ep + 1) {
+        return Some(end);
+      }
+      if count == 0 {
+        return None;
+      }
+      count -= 1;
+    }
+  }
+
+  fn min_expand(&mut self, mut s: usize, p: usize, ep: usize) -> Option<usize> {
+    loop {
+      if let Some(end) = self.do_match(s, ep + 1) {
+        return Some(end);
+      }
+      if !self.single_match(s, p, ep) {
+        return None;
+      }
+      s += 1;
+    }
+  }
+
+  fn start_capture(&mut self, s: usize, p: usize, len: CaptureLen) -> Option<usize> {
+    if self.level >= MAX_CAPTURES {
+      return None;
+    }
+
+    self.captures[self.level] = (s, len);
+    self.level += 1;
+
+    let result = self.do_match(s, p);
+    if result.is_none() {
+      self.level -= 1;
+    }
+    result
+  }
+
+  fn end_capture(&mut self, s: usize, p: usize) -> Option<usize> {
+    let level = (0..self.level)
+      .rev()
+      .find(|level| matches!(self.captures[*level].1, CaptureLen::Unclosed))?;
+
+    let (start, _) = self.captures[level];
+    self.captures[level].1 = CaptureLen::Closed(s - start);
+
+    let result = self.do_match(s, p);
+    if result.is_none() {
+      self.captures[level].1 = CaptureLen::Unclosed;
+    }
+    result
+  }
+
+  fn match_balance(&self, s: usize, p: usize) -> Option<usize> {
+    let open = self.pat[p];
+    let close = self.pat[p + 1];
+
+    if self.src.get(s) != Some(&open) {
+      return None;
+    }
+
+    let mut depth = 1;
+    for (i, c) in self.src.iter().enumerate().skip(s + 1) {
+      if *c == close {
+        depth -= 1;
+        if depth == 0 {
+          return Some(i + 1);
+        }
+      } else if *c == open {
+        depth += 1;
+      }
+    }
+
+    None
+  }
+
+  fn match_capture(&self, s: usize, index: u8) -> Option<usize> {
+    let index = (index as usize).checked_sub(b'1' as usize)?;
+    if index >= self.level {
+      return None;
+    }
+
+    let (start, CaptureLen::Closed(len)) = self.captures[index] else {
+      return None;
+    };
+
+    let captured = &self.src[start..start + len];
+    let rest = &self.src[s..];
+    (rest.len() >= len && &rest[..len] == captured).then_some(s + len)
+  }
+}
@@ -0,0 +1,70 @@
+use crate::HighlightEvent;
+
+// The unit of the offsets in `HighlightEvent::Source`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OffsetUnit {
+  // UTF-8 bytes, as produced by the highlighter and expected by the renderers
+  #[default]
+  Bytes,
+  // UTF-16 code units, e.g. for indexing JavaScript strings
+  Utf16,
+  // Unicode scalar values, e.g. for indexing Python strings
+  ScalarValues,
+}
+
+// The number of units in a slice of UTF-8, counted from the lead bytes so that slices which split
+// a character still count it once
+fn count_units(bytes: &[u8], unit: OffsetUnit) -> usize {
+  match unit {
+    OffsetUnit::Bytes => bytes.len(),
+    OffsetUnit::Utf16 => bytes
+      .iter()
+      .map(|byte| match byte {
+        0x80..=0xbf => 0,
+        // Characters outside the basic multilingual plane are encoded as a surrogate pair
+        0xf0.. => 2,
+        _ => 1,
+      })
+      .sum(),
+    OffsetUnit::ScalarValues => bytes
+      .iter()
+      .filter(|byte| !(0x80..=0xbf).contains(*byte))
+      .count(),
+  }
+}
+
+// Converts the byte offsets of the events into `unit`. Events are in source order, so the source
+// is only scanned once.
+pub fn convert_offsets(
+  events: Vec<HighlightEvent>,
+  source: &[u8],
+  unit: OffsetUnit,
+) -> Vec<HighlightEvent> {
+  if unit == OffsetUnit::Bytes {
+    return events;
+  }
+
+  let mut byte = 0;
+  let mut offset = 0;
+  let mut convert = |target: usize| {
+    let target = target.min(source.len());
+    if target < byte {
+      byte = 0;
+      offset = 0;
+    }
+    offset += count_units(&source[byte..target], unit);
+    byte = target;
+    offset
+  };
+
+  events
+    .into_iter()
+    .map(|event| match event {
+      HighlightEvent::Source { start, end } => HighlightEvent::Source {
+        start: convert(start),
+        end: convert(end),
+      },
+      event => event,
+    })
+    .collect()
+}
@@ -0,0 +1,153 @@
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  path::PathBuf,
+  sync::{Arc, Mutex},
+};
+
+use crate::{HighlightConfiguration, error::LoadError, queries};
+
+// The most configurations kept for per-call query overrides, after which the least recently used
+// is dropped
+const MAX_OVERRIDE_CONFIGS: usize = 32;
+
+// A language and its merged queries, compared in full so that distinct queries never share a
+// compiled configuration
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct OverrideKey {
+  language: String,
+  injections: String,
+  highlights: String,
+}
+
+struct CachedConfig {
+  config: Arc<HighlightConfiguration>,
+  last_used: u64,
+}
+
+// Configurations compiled with per-call query directories, keyed by the language and its merged
+// queries so that each distinct set of queries is only compiled once
+#[derive(Default)]
+pub(crate) struct OverrideCache {
+  configs: Mutex<OverrideConfigs>,
+}
+
+#[derive(Default)]
+struct OverrideConfigs {
+  configs: HashMap<OverrideKey, CachedConfig>,
+  uses: u64,
+}
+
+impl OverrideCache {
+  pub(crate) fn len(&self) -> usize {
+    self.configs.lock().unwrap().configs.len()
+  }
+
+  fn get(&self, key: &OverrideKey) -> Option<Arc<HighlightConfiguration>> {
+    let mut configs = self.configs.lock().unwrap();
+    configs.uses += 1;
+    let uses = configs.uses;
+    let cached = configs.configs.get_mut(key)?;
+    cached.last_used = uses;
+    Some(cached.config.clone())
+  }
+
+  fn insert(&self, key: OverrideKey, config: Arc<HighlightConfiguration>) {
+    let mut configs = self.configs.lock().unwrap();
+    if configs.configs.len() >= MAX_OVERRIDE_CONFIGS {
+      let least_recently_used = configs
+        .configs
+        .iter()
+        .min_by_key(|(_, cached)| cached.last_used)
+        .map(|(key, _)| key.clone());
+      if let Some(key) = least_recently_used {
+        configs.configs.remove(&key);
+      }
+    }
+
+    configs.uses += 1;
+    let last_used = configs.uses;
+    configs
+      .configs
+      .insert(key, CachedConfig { config, last_used });
+  }
+}
+
+// Query directories for a single call, layered over the highlighter's own query directories
+pub(crate) struct QueryOverrides<'a> {
+  pub(crate) query_dirs: &'a [PathBuf],
+  pub(crate) cache: &'a OverrideCache,
+  // The configuration of each language used so far in this call, so that injected layers of the
+  // same language don't read their queries again. `None` when the base configuration applies.
+  resolved: RefCell<HashMap<String, Option<Arc<HighlightConfiguration>>>>,
+}
+
+impl<'a> QueryOverrides<'a> {
+  pub(crate) fn new(query_dirs: &'a [PathBuf], cache: &'a OverrideCache) -> Self {
+    QueryOverrides {
+      query_dirs,
+      cache,
+      resolved: RefCell::default(),
+    }
+  }
+
+  // The queries are read again on every call since the files may have changed, but are only
+  // compiled when their contents haven't been seen before. Returns `None` when the overrides
+  // leave the queries unchanged, in which case `base` applies.
+  pub(crate) fn config(
+    &self,
+    base: &HighlightConfiguration,
+  ) -> Result<Option<Arc<HighlightConfiguration>>, LoadError> {
+    let grammar = &base.grammar;
+    if let Some(config) = self.resolved.borrow().get(&grammar.name) {
+      return Ok(config.clone());
+    }
+
+    let query_dirs = base
+      .query_dirs
+      .iter()
+      .chain(self.query_dirs)
+      .cloned()
+      .collect::<Vec<_>>();
+
+    let injections =
+      queries::read_query_source(grammar, &grammar.injections, &query_dirs, "injections.scm")?;
+    let highlights =
+      queries::read_query_source(grammar, &grammar.highlights, &query_dirs, "highlights.scm")?;
+
+    if injections.contents == base.injections_source
+      && highlights.contents == base.highlights_source
+    {
+      self
+        .resolved
+        .borrow_mut()
+        .insert(grammar.name.clone(), None);
+      return Ok(None);
+    }
+
+    let key = OverrideKey {
+      language: grammar.name.clone(),
+      injections: injections.contents.clone(),
+      highlights: highlights.contents.clone(),
+    };
+    let config = match self.cache.get(&key) {
+      Some(config) => config,
+      None => {
+        let config = Arc::new(HighlightConfiguration::compile(
+          grammar,
+          &query_dirs,
+          &injections,
+          &highlights,
+        )?);
+        self.cache.insert(key, config.clone());
+        config
+      }
+    };
+
+    self
+      .resolved
+      .borrow_mut()
+      .insert(grammar.name.clone(), Some(config.clone()));
+    Ok(Some(config))
+  }
+}
@@ -0,0 +1,270 @@
+use std::ops::Deref;
+use tree_sitter::{Node, Query, QueryMatch, QueryPredicate, QueryPredicateArg};
+
+use crate::lua_pattern::{LuaPattern, LuaPatternError};
+use crate::vim_regex::{VimRegex, VimRegexError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PredicateError {
+  #[error("Unknown predicate #{operator} in pattern {pattern_index}")]
+  Unknown {
+    operator: String,
+    pattern_index: usize,
+  },
+  #[error("Invalid arguments to #{operator} in pattern {pattern_index}")]
+  InvalidArguments {
+    operator: String,
+    pattern_index: usize,
+  },
+  #[error("Invalid pattern {pattern:?} in pattern {pattern_index}: {source}")]
+  InvalidLuaPattern {
+    pattern: String,
+    pattern_index: usize,
+    source: LuaPatternError,
+  },
+  #[error("Unsupported regex {pattern:?} in pattern {pattern_index}: {source}")]
+  UnsupportedRegex {
+    pattern: String,
+    pattern_index: usize,
+    source: VimRegexError,
+  },
+}
+
+impl PredicateError {
+  pub fn pattern_index(&self) -> usize {
+    match self {
+      PredicateError::Unknown { pattern_index, .. }
+      | PredicateError::InvalidArguments { pattern_index, .. }
+      | PredicateError::InvalidLuaPattern { pattern_index, .. }
+      | PredicateError::UnsupportedRegex { pattern_index, .. } => *pattern_index,
+    }
+  }
+
+  // Predicates which may be valid in Neovim but which we can't evaluate. Rather than failing the
+  // whole query, only the pattern using them is left out.
+  pub fn is_unsupported(&self) -> bool {
+    matches!(
+      self,
+      PredicateError::Unknown { .. } | PredicateError::UnsupportedRegex { .. }
+    )
+  }
+}
+
+enum Check {
+  LuaMatch { pattern: LuaPattern, any: bool },
+  VimMatch { regex: VimRegex, any: bool },
+  Contains(Vec<String>),
+  HasAncestor(Vec<String>),
+  HasParent(Vec<String>),
+  KindEq(Vec<String>),
+}
+
+struct Predicate {
+  capture: u32,
+  negated: bool,
+  check: Check,
+}
+
+fn string_args(args: &[QueryPredicateArg]) -> Option<Vec<String>> {
+  if args.is_empty() {
+    return None;
+  }
+
+  args
+    .iter()
+    .map(|arg| match arg {
+      QueryPredicateArg::String(value) => Some(value.to_string()),
+      QueryPredicateArg::Capture(_) => None,
+    })
+    .collect()
+}
+
+fn parse_predicate(
+  pred: &QueryPredicate,
+  pattern_index: usize,
+) -> Result<Option<Predicate>, PredicateError> {
+  let operator = pred.operator.deref();
+  // Directives modify a match rather than filter it. They are consumed by the passes which
+  // understand them (e.g. `offset!` by the injection pass) and otherwise ignored.
+  if operator.ends_with('!') {
+    return Ok(None);
+  }
+
+  let (negated, name) = match operator.strip_prefix("not-") {
+    Some(name) => (true, name),
+    None => (false, operator),
+  };
+
+  let invalid_arguments = || PredicateError::InvalidArguments {
+    operator: operator.to_string(),
+    pattern_index,
+  };
+
+  let (capture, args) = match pred.args.split_first() {
+    Some((QueryPredicateArg::Capture(capture), args)) => (Some(*capture), args),
+    _ => (None, &[][..]),
+  };
+
+  let check = match name {
+    "lua-match?" | "any-lua-match?" => {
+      let [QueryPredicateArg::String(pattern)] = args else {
+        return Err(invalid_arguments());
+      };
+      let pattern =
+        LuaPattern::new(pattern).map_err(|source| PredicateError::InvalidLuaPattern {
+          pattern: pattern.to_string(),
+          pattern_index,
+          source,
+        })?;
+      Check::LuaMatch {
+        pattern,
+        any: name == "any-lua-match?",
+      }
+    }
+    "vim-match?" | "any-vim-match?" => {
+      let [QueryPredicateArg::String(pattern)] = args else {
+        return Err(invalid_arguments());
+      };
+      let regex = VimRegex::new(pattern).map_err(|source| PredicateError::UnsupportedRegex {
+        pattern: pattern.to_string(),
+        pattern_index,
+        source,
+      })?;
+      Check::VimMatch {
+        regex,
+        any: name == "any-vim-match?",
+      }
+    }
+    "contains?" | "any-contains?" => {
+      Check::Contains(string_args(args).ok_or_else(invalid_arguments)?)
+    }
+    "has-ancestor?" => Check::HasAncestor(string_args(args).ok_or_else(invalid_arguments)?),
+    "has-parent?" => Check::HasParent(string_args(args).ok_or_else(invalid_arguments)?),
+    "kind-eq?" => Check::KindEq(string_args(args).ok_or_else(invalid_arguments)?),
+    _ => {
+      return Err(PredicateError::Unknown {
+        operator: operator.to_string(),
+        pattern_index,
+      });
+    }
+  };
+
+  let Some(capture) = capture else {
+    return Err(invalid_arguments());
+  };
+
+  Ok(Some(Predicate {
+    capture,
+    negated,
+    check,
+  }))
+}
+
+fn has_ancestor(node: &Node, kinds: &[String]) -> bool {
+  let mut current = node.parent();
+  while let Some(ancestor) = current {
+    if kinds.iter().any(|kind| kind == ancestor.kind()) {
+      return true;
+    }
+    current = ancestor.parent();
+  }
+  false
+}
+
+impl Predicate {
+  // Mirrors the predicate handlers in Neovim's `vim.treesitter.query`. A predicate on a capture
+  // which has no nodes in this match (e.g. a quantified capture) is always satisfied.
+  fn evaluate(&self, query_match: &QueryMatch, source: &[u8]) -> bool {
+    let mut nodes = query_match.nodes_for_capture_index(self.capture).peekable();
+
+    if nodes.peek().is_none() {
+      return true;
+    }
+
+    let text = |node: &Node| &source[node.byte_range()];
+
+    let result = match &self.check {
+      Check::LuaMatch { pattern, any } => {
+        if *any {
+          nodes.any(|node| pattern.is_match(text(&node)))
+        } else {
+          nodes.all(|node| pattern.is_match(text(&node)))
+        }
+      }
+      Check::VimMatch { regex, any } => {
+        if *any {
+          nodes.any(|node| regex.is_match(text(&node)))
+        } else {
+          nodes.all(|node| regex.is_match(text(&node)))
+        }
+      }
+      Check::Contains(needles) => nodes.any(|node| {
+        let text = text(&node);
+        needles.iter().any(|needle| {
+          needle.is_empty()
+            || text
+              .windows(needle.len())
+              .any(|window| window == needle.as_bytes())
+        })
+      }),
+      Check::HasAncestor(kinds) => nodes.any(|node| has_ancestor(&node, kinds)),
+      Check::HasParent(kinds) => nodes.any(|node| {
+        node
+          .parent()
+          .is_some_and(|parent| kinds.iter().any(|kind| kind == parent.kind()))
+      }),
+      Check::KindEq(kinds) => nodes.all(|node| kinds.iter().any(|kind| kind == node.kind())),
+    };
+
+    result != self.negated
+  }
+}
+
+// The general predicates of each pattern in a query, parsed once when the query is loaded
+pub(crate) struct QueryPredicates {
+  patterns: Vec<PatternPredicates>,
+}
+
+#[derive(Default)]
+struct PatternPredicates {
+  predicates: Vec<Predicate>,
+  // Set when the pattern uses a predicate we can't evaluate, so that it never matches
+  unsupported: bool,
+}
+
+impl QueryPredicates {
+  // Fails on the first predicate which is invalid, e.g. with the wrong arguments, so that typos
+  // surface when the query is loaded instead of silently matching. Predicates which we can't
+  // evaluate are returned instead, and only the patterns using them never match.
+  pub(crate) fn new(query: &Query) -> Result<(Self, Vec<PredicateError>), PredicateError> {
+    let mut unsupported = Vec::new();
+    let mut patterns = Vec::with_capacity(query.pattern_count());
+
+    for pattern_index in 0..query.pattern_count() {
+      let mut pattern = PatternPredicates::default();
+      for pred in query.general_predicates(pattern_index) {
+        match parse_predicate(pred, pattern_index) {
+          Ok(Some(predicate)) => pattern.predicates.push(predicate),
+          Ok(None) => {}
+          Err(err) if err.is_unsupported() => {
+            pattern.unsupported = true;
+            unsupported.push(err);
+          }
+          Err(err) => return Err(err),
+        }
+      }
+      patterns.push(pattern);
+    }
+
+    Ok((QueryPredicates { patterns }, unsupported))
+  }
+
+  pub(crate) fn satisfied(&self, query_match: &QueryMatch, source: &[u8]) -> bool {
+    let pattern = &self.patterns[query_match.pattern_index];
+    !pattern.unsupported
+      && pattern
+        .predicates
+        .iter()
+        .all(|predicate| predicate.evaluate(query_match, source))
+  }
+}
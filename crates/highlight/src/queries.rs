@@ -1,78 +1,275 @@
-use anyhow::Result;
-use std::{fs, path::PathBuf};
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
 use tree_sitter::Query;
 
-use crate::grammar;
+use crate::{
+  error::{LoadError, QueryError, QueryErrorKind},
+  grammar, predicates,
+};
 
-fn read_files(paths: &[PathBuf]) -> Result<String> {
-  let mut out = String::new();
-  for (i, p) in paths.iter().enumerate() {
-    let contents = fs::read_to_string(p)
-      .map_err(|e| anyhow::format_err!("Failed to read {}: {e}", p.display()))?;
-    if i > 0 {
-      out.push('\n');
+// A file which was merged into a query, starting at `row` and byte `start` of the merged query
+struct Segment {
+  path: PathBuf,
+  row: usize,
+  start: usize,
+}
+
+// The contents of a query merged from several files, along with a map of where each file starts
+// so that positions in the merged query can be traced back to the file they came from
+#[derive(Default)]
+pub(crate) struct QuerySource {
+  pub(crate) contents: String,
+  segments: Vec<Segment>,
+}
+
+impl QuerySource {
+  // Appends a file on a new line
+  fn push(&mut self, path: &Path, contents: &str) {
+    if contents.is_empty() {
+      return;
+    }
+
+    if !self.contents.is_empty() && !self.contents.ends_with('\n') {
+      self.contents.push('\n');
     }
-    out.push_str(&contents);
+
+    self.segments.push(Segment {
+      path: path.to_path_buf(),
+      row: self.contents.matches('\n').count(),
+      start: self.contents.len(),
+    });
+    self.contents.push_str(contents);
   }
-  Ok(out)
-}
 
-fn merge_queries(base: &str, overlay: &str) -> String {
-  if base.is_empty() {
-    return overlay.to_owned();
+  fn append(&mut self, other: QuerySource) {
+    for (index, segment) in other.segments.iter().enumerate() {
+      let end = other
+        .segments
+        .get(index + 1)
+        .map_or(other.contents.len(), |next| next.start);
+      self.push(&segment.path, &other.contents[segment.start..end]);
+    }
   }
 
-  if overlay.is_empty() {
-    return base.to_owned();
+  // The file containing a row of the merged query and the row within that file
+  fn locate(&self, row: usize) -> Option<(&Path, usize)> {
+    self
+      .segments
+      .iter()
+      .rev()
+      .find(|segment| segment.row <= row)
+      .map(|segment| (segment.path.as_path(), row - segment.row))
   }
+}
+
+fn read_file(language: &str, path: &Path) -> Result<String, LoadError> {
+  fs::read_to_string(path).map_err(|err| LoadError::QueryFile {
+    language: language.to_string(),
+    path: path.to_path_buf(),
+    reason: err.to_string(),
+  })
+}
 
-  let mut merged = String::with_capacity(base.len() + overlay.len() + 1);
-  merged.push_str(base);
-  if !base.ends_with('\n') {
-    merged.push('\n');
+fn read_files(language: &str, paths: &[PathBuf]) -> Result<QuerySource, LoadError> {
+  let mut source = QuerySource::default();
+  for path in paths {
+    source.push(path, &read_file(language, path)?);
   }
-  merged.push_str(overlay);
+  Ok(source)
+}
 
-  merged
+// The nvim-treesitter modelines in the comments at the top of a query file, i.e. `;; extends` and
+// `; inherits: ecma,(jsx)`
+#[derive(Default)]
+struct Modeline {
+  extends: bool,
+  inherits: Vec<String>,
 }
 
-fn is_extending(contents: &str) -> bool {
-  contents
-    .lines()
-    .next()
-    .map(|line| line.trim_start().starts_with(";; extends"))
-    .unwrap_or(false)
+fn parse_modeline(contents: &str) -> Modeline {
+  let mut modeline = Modeline::default();
+
+  for line in contents.lines() {
+    let Some(comment) = line.trim_start().strip_prefix(';') else {
+      break;
+    };
+    let comment = comment.trim_start_matches(';').trim();
+
+    if comment.starts_with("extends") {
+      modeline.extends = true;
+    } else if let Some(languages) = comment.strip_prefix("inherits") {
+      let languages = languages.trim_start();
+      let languages = languages.strip_prefix(':').unwrap_or(languages);
+      modeline.inherits.extend(
+        languages
+          .split(',')
+          .map(str::trim)
+          .filter(|language| !language.is_empty())
+          .map(String::from),
+      );
+    }
+  }
+
+  modeline
 }
 
-fn read_query(queries_dirs: &[PathBuf], name: &str, filename: &str, base: &str) -> Result<String> {
-  let mut result = base.to_owned();
+// Reads the queries of the last language in `chain` from each of the query directories. A file
+// replaces the queries before it unless it extends them, and is preceded by the queries of the
+// languages it inherits from. `chain` starts with the grammar's own language and holds each
+// language being inherited from, to detect cycles.
+fn read_query(
+  queries_dirs: &[PathBuf],
+  chain: &mut Vec<String>,
+  filename: &str,
+  base: QuerySource,
+) -> Result<QuerySource, LoadError> {
+  let language = chain.last().expect("language").clone();
+  let mut result = base;
 
   for dir in queries_dirs {
-    let path = dir.join(name).join(filename);
-    if path.is_file() {
-      let contents = fs::read_to_string(&path)
-        .map_err(|e| anyhow::format_err!("Failed to read {}: {e}", path.display()))?;
+    let path = dir.join(&language).join(filename);
+    if !path.is_file() {
+      continue;
+    }
+
+    let contents = read_file(&chain[0], &path)?;
+    let modeline = parse_modeline(&contents);
 
-      if is_extending(&contents) {
-        result = merge_queries(&result, &contents);
+    let mut inherits = Vec::new();
+    let mut extends = modeline.extends;
+    for inherited in &modeline.inherits {
+      let inherited = match inherited
+        .strip_prefix('(')
+        .and_then(|name| name.strip_suffix(')'))
+      {
+        // Like Neovim, optional languages are only inherited by the language being loaded and
+        // not by the languages it inherits from
+        Some(_) if chain.len() > 1 => continue,
+        Some(name) => name,
+        None => inherited.as_str(),
+      };
+
+      // A language inheriting from itself extends its own queries
+      if inherited == language {
+        extends = true;
       } else {
-        result = contents;
+        inherits.push(inherited);
       }
     }
+
+    if !extends {
+      result = QuerySource::default();
+    }
+
+    for inherited in inherits {
+      if chain.iter().any(|language| language == inherited) {
+        let mut cycle = chain.clone();
+        cycle.push(inherited.to_string());
+        return Err(LoadError::InheritanceCycle {
+          language: chain[0].clone(),
+          query: filename.to_string(),
+          chain: cycle,
+        });
+      }
+
+      chain.push(inherited.to_string());
+      let queries = read_query(queries_dirs, chain, filename, QuerySource::default());
+      chain.pop();
+      result.append(queries?);
+    }
+
+    result.push(&path, &contents);
   }
 
   Ok(result)
 }
 
-pub fn load_query(
+// The row and column of a byte offset into the query
+fn position(contents: &str, offset: usize) -> (usize, usize) {
+  let before = &contents[..offset.min(contents.len())];
+  let row = before.matches('\n').count();
+  let column = before.len() - before.rfind('\n').map_or(0, |newline| newline + 1);
+  (row, column)
+}
+
+// Reads the grammar's own query files followed by those in each of the query directories
+pub(crate) fn read_query_source(
   grammar: &grammar::LoadedGrammar,
   base_files: &[PathBuf],
   queries_dirs: &[PathBuf],
   file_name: &str,
-) -> Result<Query> {
-  let base_queries = read_files(base_files)?;
+) -> Result<QuerySource, LoadError> {
+  let base_queries = read_files(&grammar.name, base_files)?;
+
+  let mut chain = vec![grammar.name.clone()];
+  read_query(queries_dirs, &mut chain, file_name, base_queries)
+}
 
-  let query_content = read_query(queries_dirs, &grammar.name, file_name, &base_queries)?;
+// A compiled query along with its parsed predicates, and a diagnostic for each pattern which was
+// left out because it uses predicates we can't evaluate
+pub(crate) struct CompiledQuery {
+  pub(crate) query: Query,
+  pub(crate) predicates: predicates::QueryPredicates,
+  pub(crate) warnings: Vec<QueryError>,
+}
 
-  Query::new(&grammar.lang, &query_content).map_err(|err| anyhow::format_err!("{err:?}"))
+pub(crate) fn compile_query(
+  grammar: &grammar::LoadedGrammar,
+  source: &QuerySource,
+  file_name: &str,
+) -> Result<CompiledQuery, LoadError> {
+  // Positions are reported in the file the error came from rather than in the merged query. Files
+  // start on a line of their own, so only the row changes.
+  let query_error = |row: usize, column, kind, message| {
+    let (path, row) = match source.locate(row) {
+      Some((path, row)) => (Some(path.to_path_buf()), row),
+      None => (None, row),
+    };
+    QueryError {
+      language: grammar.name.clone(),
+      query: file_name.to_string(),
+      path,
+      row,
+      column,
+      kind,
+      message,
+    }
+  };
+
+  let query = Query::new(&grammar.lang, &source.contents).map_err(|err| {
+    query_error(
+      err.row,
+      err.column,
+      QueryErrorKind::from(&err.kind),
+      err.message,
+    )
+  })?;
+  let predicate_error = |err: predicates::PredicateError| {
+    let (row, column) = position(
+      &source.contents,
+      query.start_byte_for_pattern(err.pattern_index()),
+    );
+    query_error(row, column, QueryErrorKind::Predicate, err.to_string())
+  };
+  let (predicates, unsupported) =
+    predicates::QueryPredicates::new(&query).map_err(predicate_error)?;
+  let warnings = unsupported.into_iter().map(predicate_error).collect();
+
+  Ok(CompiledQuery {
+    query,
+    predicates,
+    warnings,
+  })
+}
+
+pub fn load_query(
+  grammar: &grammar::LoadedGrammar,
+  base_files: &[PathBuf],
+  queries_dirs: &[PathBuf],
+  file_name: &str,
+) -> Result<Query, LoadError> {
+  let source = read_query_source(grammar, base_files, queries_dirs, file_name)?;
+  compile_query(grammar, &source, file_name).map(|compiled| compiled.query)
 }
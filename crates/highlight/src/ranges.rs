@@ -1,5 +1,5 @@
 use std::borrow::Cow;
-use tree_sitter::{Point, Range};
+use tree_sitter::{Node, Point, Range};
 
 fn point_for_byte(source: &[u8], byte_index: usize) -> Point {
   let target = byte_index.min(source.len());
@@ -74,3 +74,170 @@ pub fn remap_injected_region_highlight_range(
     },
   }
 }
+
+// When the source had a newline appended, an included range which ran to the end of the original
+// source is extended to cover the newline so that the parser still sees it.
+pub fn extend_ranges_for_appended_newline(
+  ranges: &[Range],
+  original_endpoint: &Option<EndPoint>,
+) -> Vec<Range> {
+  let mut ranges = ranges.to_vec();
+
+  if let Some((end_byte, end_point)) = original_endpoint
+    && let Some(last) = ranges.last_mut()
+    && last.end_byte >= *end_byte
+  {
+    last.end_byte = end_byte + 1;
+    last.end_point = Point {
+      row: end_point.row + 1,
+      column: 0,
+    };
+  }
+
+  ranges
+}
+
+// Sorts the given ranges and merges any which overlap or touch so that they are valid included
+// ranges.
+pub fn normalize_ranges(mut ranges: Vec<Range>) -> Vec<Range> {
+  ranges.sort_by(|a, b| {
+    a.start_byte
+      .cmp(&b.start_byte)
+      .then(a.end_byte.cmp(&b.end_byte))
+  });
+
+  let mut normalized: Vec<Range> = Vec::with_capacity(ranges.len());
+  for range in ranges {
+    if let Some(last) = normalized.last_mut()
+      && range.start_byte <= last.end_byte
+    {
+      if range.end_byte > last.end_byte {
+        last.end_byte = range.end_byte;
+        last.end_point = range.end_point;
+      }
+      continue;
+    }
+    normalized.push(range);
+  }
+
+  normalized
+}
+
+pub fn enclosing_range(ranges: &[Range]) -> Option<Range> {
+  let first = ranges.first()?;
+  let last = ranges.last()?;
+
+  Some(Range {
+    start_byte: first.start_byte,
+    start_point: first.start_point,
+    end_byte: last.end_byte,
+    end_point: last.end_point,
+  })
+}
+
+// Converts a range within the parent source into a range relative to the start of `outer`
+pub fn relative_range(outer: &Range, range: &Range) -> Range {
+  let relative_point = |point: Point| Point {
+    row: point.row - outer.start_point.row,
+    column: if point.row == outer.start_point.row {
+      point.column - outer.start_point.column
+    } else {
+      point.column
+    },
+  };
+
+  Range {
+    start_byte: range.start_byte - outer.start_byte,
+    end_byte: range.end_byte - outer.start_byte,
+    start_point: relative_point(range.start_point),
+    end_point: relative_point(range.end_point),
+  }
+}
+
+// Splits a highlight range from an injected layer into the parts which fall within the layer's
+// included ranges, so that text between the ranges keeps the highlights of the parent layer.
+pub fn clip_to_ranges(range: &Range, included_ranges: &[Range]) -> Vec<Range> {
+  if let [included] = included_ranges
+    && included.start_byte <= range.start_byte
+    && range.end_byte <= included.end_byte
+  {
+    return vec![*range];
+  }
+
+  included_ranges
+    .iter()
+    .filter_map(|included| {
+      let start_byte = range.start_byte.max(included.start_byte);
+      let end_byte = range.end_byte.min(included.end_byte);
+
+      let overlaps = start_byte < end_byte
+        || (range.start_byte == range.end_byte
+          && included.start_byte <= range.start_byte
+          && range.start_byte <= included.end_byte);
+      if !overlaps {
+        return None;
+      }
+
+      Some(Range {
+        start_byte,
+        end_byte,
+        start_point: if start_byte == range.start_byte {
+          range.start_point
+        } else {
+          included.start_point
+        },
+        end_point: if end_byte == range.end_byte {
+          range.end_point
+        } else {
+          included.end_point
+        },
+      })
+    })
+    .collect()
+}
+
+// Following Neovim, the content of an injection is the captured range minus the ranges of the
+// node's named children, unless `injection.include-children` is set.
+pub fn exclude_children(node: &Node, range: Range) -> Vec<Range> {
+  if node.named_child_count() == 0 {
+    return vec![range];
+  }
+
+  let mut ranges = Vec::new();
+  let mut start_byte = range.start_byte;
+  let mut start_point = range.start_point;
+
+  let mut cursor = node.walk();
+  for child in node.named_children(&mut cursor) {
+    let child_range = child.range();
+    if child_range.end_byte <= start_byte {
+      continue;
+    }
+    if child_range.start_byte >= range.end_byte {
+      break;
+    }
+
+    if start_byte < child_range.start_byte {
+      ranges.push(Range {
+        start_byte,
+        start_point,
+        end_byte: child_range.start_byte,
+        end_point: child_range.start_point,
+      });
+    }
+
+    start_byte = child_range.end_byte;
+    start_point = child_range.end_point;
+  }
+
+  if start_byte < range.end_byte {
+    ranges.push(Range {
+      start_byte,
+      start_point,
+      end_byte: range.end_byte,
+      end_point: range.end_point,
+    });
+  }
+
+  ranges
+}
@@ -0,0 +1,99 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::languages::{LanguageEntry, Languages};
+
+// Maps the language names found in markdown info strings, injection captures and calls to
+// `Highlighter::highlight` (e.g. `js`, `JavaScript`, `clj`, `main.py`) onto a loaded language.
+#[derive(Debug, Default, Clone)]
+pub struct LanguageResolver {
+  aliases: HashMap<String, String>,
+}
+
+fn sorted(languages: &Languages) -> Vec<(&String, &LanguageEntry)> {
+  let mut languages = languages.iter().collect::<Vec<_>>();
+  languages.sort_by_key(|(lang, _)| *lang);
+  languages
+}
+
+fn find_by_file_type<'a>(languages: &'a Languages, file_type: &str) -> Option<&'a str> {
+  sorted(languages)
+    .into_iter()
+    .find(|(_, language)| language.file_types.iter().any(|ty| ty == file_type))
+    .map(|(lang, _)| lang.as_str())
+}
+
+// Like the tree-sitter CLI, the grammar whose `injection-regex` produces the longest match wins
+fn find_by_injection_regex<'a>(languages: &'a Languages, name: &str) -> Option<&'a str> {
+  let mut best: Option<(&str, usize)> = None;
+
+  for (lang, language) in sorted(languages) {
+    let Some(regex) = &language.injection_regex else {
+      continue;
+    };
+    let Some(found) = regex.find(name) else {
+      continue;
+    };
+
+    let length = found.end() - found.start();
+    if best.is_none_or(|(_, best_length)| length > best_length) {
+      best = Some((lang.as_str(), length));
+    }
+  }
+
+  best.map(|(lang, _)| lang)
+}
+
+impl LanguageResolver {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn alias(&mut self, alias: &str, language: &str) {
+    self
+      .aliases
+      .insert(alias.to_lowercase(), language.to_string());
+  }
+
+  pub(crate) fn resolve<'a>(&self, languages: &'a Languages, name: &str) -> Option<&'a str> {
+    if let Some((lang, _)) = languages.get_key_value(name) {
+      return Some(lang.as_str());
+    }
+
+    let lowercase = name.to_lowercase();
+
+    if let Some(alias) = self.aliases.get(&lowercase)
+      && let Some((lang, _)) = languages.get_key_value(alias)
+    {
+      return Some(lang.as_str());
+    }
+
+    if let Some((lang, _)) = sorted(languages)
+      .into_iter()
+      .find(|(lang, _)| lang.to_lowercase() == lowercase)
+    {
+      return Some(lang.as_str());
+    }
+
+    find_by_injection_regex(languages, name)
+      .or_else(|| find_by_injection_regex(languages, &lowercase))
+      .or_else(|| find_by_file_type(languages, name))
+      .or_else(|| find_by_file_type(languages, &lowercase))
+      .or_else(|| self.resolve_filename(languages, name))
+  }
+
+  // Resolves a filename using the `file-types` of each grammar, which may list either whole
+  // filenames or extensions.
+  pub(crate) fn resolve_filename<'a>(
+    &self,
+    languages: &'a Languages,
+    filename: &str,
+  ) -> Option<&'a str> {
+    let name = Path::new(filename).file_name()?.to_str()?;
+
+    find_by_file_type(languages, name).or_else(|| {
+      let extension = Path::new(name).extension()?.to_str()?;
+      find_by_file_type(languages, extension)
+        .or_else(|| find_by_file_type(languages, &extension.to_lowercase()))
+    })
+  }
+}
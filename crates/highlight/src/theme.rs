@@ -0,0 +1,434 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+  Rgb(u8, u8, u8),
+  // An index into the terminal's 256 colour palette, where 0-15 are the basic ANSI colours
+  Indexed(u8),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Style {
+  pub fg: Option<Color>,
+  pub bg: Option<Color>,
+  pub bold: bool,
+  pub dim: bool,
+  pub italic: bool,
+  pub underline: bool,
+  pub strikethrough: bool,
+  pub reversed: bool,
+}
+
+// Maps capture names to styles. A capture without a style of its own uses the style of its closest
+// parent, so `function.builtin` falls back to `function`.
+#[derive(Debug, Clone, Default)]
+pub struct Theme {
+  styles: HashMap<String, Style>,
+}
+
+// Looks up `name`, falling back through its parents (`function.builtin` -> `function`)
+pub(crate) fn lookup_hierarchical<'a, T>(map: &'a HashMap<String, T>, name: &str) -> Option<&'a T> {
+  let mut name = name;
+  loop {
+    if let Some(value) = map.get(name) {
+      return Some(value);
+    }
+    name = &name[..name.rfind('.')?];
+  }
+}
+
+impl Theme {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn insert(&mut self, capture: &str, style: Style) {
+    self.styles.insert(capture.to_string(), style);
+  }
+
+  pub fn get(&self, capture: &str) -> Option<&Style> {
+    lookup_hierarchical(&self.styles, capture)
+  }
+
+  pub fn styles(&self) -> impl Iterator<Item = (&str, &Style)> {
+    self
+      .styles
+      .iter()
+      .map(|(capture, style)| (capture.as_str(), style))
+  }
+}
+
+impl FromIterator<(String, Style)> for Theme {
+  fn from_iter<T: IntoIterator<Item = (String, Style)>>(iter: T) -> Self {
+    Self {
+      styles: iter.into_iter().collect(),
+    }
+  }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ThemeError {
+  #[error("Invalid TOML theme: {0}")]
+  Toml(#[from] toml::de::Error),
+  #[error("Invalid JSON theme: {0}")]
+  Json(#[from] serde_json::Error),
+  #[error("Theme must be a table of capture names to styles")]
+  NotATable,
+  #[error("Invalid colour {color:?} for {capture}")]
+  InvalidColor { capture: String, color: String },
+  #[error("Invalid style for {capture}: {reason}")]
+  InvalidStyle { capture: String, reason: String },
+}
+
+type Palette = HashMap<String, String>;
+
+// The names Helix uses for the 16 basic terminal colours
+fn named_color(name: &str) -> Option<Color> {
+  let index = match name {
+    "black" => 0,
+    "red" => 1,
+    "green" => 2,
+    "yellow" => 3,
+    "blue" => 4,
+    "magenta" => 5,
+    "cyan" => 6,
+    "light-gray" => 7,
+    "gray" => 8,
+    "light-red" => 9,
+    "light-green" => 10,
+    "light-yellow" => 11,
+    "light-blue" => 12,
+    "light-magenta" => 13,
+    "light-cyan" => 14,
+    "white" => 15,
+    _ => return None,
+  };
+  Some(Color::Indexed(index))
+}
+
+impl Color {
+  // Parses `#rrggbb`, `#rgb` or one of the basic colour names
+  pub fn parse(value: &str) -> Option<Color> {
+    let Some(hex) = value.strip_prefix('#') else {
+      return named_color(value);
+    };
+
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+    match hex.len() {
+      6 => Some(Color::Rgb(
+        channel(&hex[0..2])?,
+        channel(&hex[2..4])?,
+        channel(&hex[4..6])?,
+      )),
+      3 => Some(Color::Rgb(
+        channel(&hex[0..1])? * 17,
+        channel(&hex[1..2])? * 17,
+        channel(&hex[2..3])? * 17,
+      )),
+      _ => None,
+    }
+  }
+
+  pub fn to_hex(self) -> String {
+    let (r, g, b) = self.to_rgb();
+    format!("#{r:02x}{g:02x}{b:02x}")
+  }
+}
+
+impl Style {
+  // The style as CSS declarations, e.g. `color: #ff0000; font-weight: bold`
+  pub fn to_css(&self) -> String {
+    let mut declarations = Vec::new();
+
+    if let Some(fg) = self.fg {
+      declarations.push(format!("color: {}", fg.to_hex()));
+    }
+    if let Some(bg) = self.bg {
+      declarations.push(format!("background-color: {}", bg.to_hex()));
+    }
+    if self.bold {
+      declarations.push("font-weight: bold".to_string());
+    }
+    if self.italic {
+      declarations.push("font-style: italic".to_string());
+    }
+    if self.dim {
+      declarations.push("opacity: 0.7".to_string());
+    }
+
+    let decorations = [
+      (self.underline, "underline"),
+      (self.strikethrough, "line-through"),
+    ]
+    .into_iter()
+    .filter_map(|(enabled, decoration)| enabled.then_some(decoration))
+    .collect::<Vec<_>>();
+    if !decorations.is_empty() {
+      declarations.push(format!("text-decoration: {}", decorations.join(" ")));
+    }
+
+    declarations.join("; ")
+  }
+}
+
+fn parse_color(
+  capture: &str,
+  value: &serde_json::Value,
+  palette: &Palette,
+) -> Result<Color, ThemeError> {
+  let invalid = || ThemeError::InvalidColor {
+    capture: capture.to_string(),
+    color: value.to_string(),
+  };
+
+  let name = value.as_str().ok_or_else(invalid)?;
+  let name = palette.get(name).map(String::as_str).unwrap_or(name);
+  Color::parse(name).ok_or_else(invalid)
+}
+
+// Colours which defer to the terminal or the page, rather than setting one
+fn is_default_color(value: &serde_json::Value) -> bool {
+  matches!(value.as_str(), Some("default" | "reset" | "none" | "NONE"))
+}
+
+fn parse_helix_style(
+  capture: &str,
+  value: &serde_json::Value,
+  palette: &Palette,
+) -> Result<Style, ThemeError> {
+  let invalid = |reason: &str| ThemeError::InvalidStyle {
+    capture: capture.to_string(),
+    reason: reason.to_string(),
+  };
+
+  let mut style = Style::default();
+
+  let table = match value {
+    serde_json::Value::String(_) => {
+      if !is_default_color(value) {
+        style.fg = Some(parse_color(capture, value, palette)?);
+      }
+      return Ok(style);
+    }
+    serde_json::Value::Object(table) => table,
+    _ => return Err(invalid("expected a colour or a table")),
+  };
+
+  for (key, value) in table {
+    match key.as_str() {
+      "fg" | "bg" if is_default_color(value) => {}
+      "fg" => style.fg = Some(parse_color(capture, value, palette)?),
+      "bg" => style.bg = Some(parse_color(capture, value, palette)?),
+      // An underline colour can't be represented, so any underline style just underlines
+      "underline" => style.underline = true,
+      "modifiers" => {
+        let modifiers = value
+          .as_array()
+          .ok_or_else(|| invalid("modifiers must be a list"))?;
+        for modifier in modifiers {
+          match modifier.as_str() {
+            Some("bold") => style.bold = true,
+            Some("dim") => style.dim = true,
+            Some("italic") => style.italic = true,
+            Some("underlined") => style.underline = true,
+            Some("crossed_out") => style.strikethrough = true,
+            Some("reversed") => style.reversed = true,
+            Some("slow_blink" | "rapid_blink" | "hidden") => {}
+            _ => return Err(invalid(&format!("unknown modifier {modifier}"))),
+          }
+        }
+      }
+      key => return Err(invalid(&format!("unknown key {key:?}"))),
+    }
+  }
+
+  Ok(style)
+}
+
+fn parse_simple_style(capture: &str, value: &serde_json::Value) -> Result<Style, ThemeError> {
+  let invalid = |reason: &str| ThemeError::InvalidStyle {
+    capture: capture.to_string(),
+    reason: reason.to_string(),
+  };
+
+  let palette = Palette::new();
+  let mut style = Style::default();
+
+  let table = match value {
+    serde_json::Value::String(_) => {
+      style.fg = Some(parse_color(capture, value, &palette)?);
+      return Ok(style);
+    }
+    serde_json::Value::Object(table) => table,
+    _ => return Err(invalid("expected a colour or a table")),
+  };
+
+  for (key, value) in table {
+    let flag = || {
+      value
+        .as_bool()
+        .ok_or_else(|| invalid(&format!("{key} must be a boolean")))
+    };
+
+    match key.as_str() {
+      "fg" | "bg" if is_default_color(value) => {}
+      "fg" => style.fg = Some(parse_color(capture, value, &palette)?),
+      "bg" => style.bg = Some(parse_color(capture, value, &palette)?),
+      "bold" => style.bold = flag()?,
+      "dim" => style.dim = flag()?,
+      "italic" => style.italic = flag()?,
+      "underline" => style.underline = flag()?,
+      "strikethrough" => style.strikethrough = flag()?,
+      "reverse" | "reversed" => style.reversed = flag()?,
+      key => return Err(invalid(&format!("unknown key {key:?}"))),
+    }
+  }
+
+  Ok(style)
+}
+
+impl Theme {
+  // Parses a Helix theme. Colours may name an entry of the theme's `[palette]`, and `inherits` is
+  // ignored as the parent theme isn't available.
+  pub fn from_helix_toml(source: &str) -> Result<Theme, ThemeError> {
+    let value = toml::from_str::<serde_json::Value>(source)?;
+    let serde_json::Value::Object(table) = value else {
+      return Err(ThemeError::NotATable);
+    };
+
+    let palette = match table.get("palette") {
+      Some(serde_json::Value::Object(palette)) => palette
+        .iter()
+        .filter_map(|(name, color)| Some((name.clone(), color.as_str()?.to_string())))
+        .collect(),
+      _ => Palette::new(),
+    };
+
+    table
+      .iter()
+      .filter(|(key, _)| !matches!(key.as_str(), "palette" | "inherits" | "rainbow"))
+      .map(|(capture, value)| {
+        Ok((
+          capture.clone(),
+          parse_helix_style(capture, value, &palette)?,
+        ))
+      })
+      .collect()
+  }
+
+  // Parses a table of capture names to either a colour or a table of `fg`, `bg` and boolean
+  // attributes, as passed to Neovim's `nvim_set_hl`. Capture names may be written with or without
+  // their leading `@`.
+  pub fn from_json(source: &str) -> Result<Theme, ThemeError> {
+    Self::from_simple(serde_json::from_str(source)?)
+  }
+
+  // The TOML equivalent of `Theme::from_json`
+  pub fn from_toml(source: &str) -> Result<Theme, ThemeError> {
+    Self::from_simple(toml::from_str(source)?)
+  }
+
+  fn from_simple(value: serde_json::Value) -> Result<Theme, ThemeError> {
+    let serde_json::Value::Object(table) = value else {
+      return Err(ThemeError::NotATable);
+    };
+
+    table
+      .iter()
+      .map(|(capture, value)| {
+        let capture = capture.strip_prefix('@').unwrap_or(capture);
+        Ok((capture.to_string(), parse_simple_style(capture, value)?))
+      })
+      .collect()
+  }
+}
+
+// The xterm defaults for the 16 basic colours
+const ANSI_16: [(u8, u8, u8); 16] = [
+  (0, 0, 0),
+  (205, 0, 0),
+  (0, 205, 0),
+  (205, 205, 0),
+  (0, 0, 238),
+  (205, 0, 205),
+  (0, 205, 205),
+  (229, 229, 229),
+  (127, 127, 127),
+  (255, 0, 0),
+  (0, 255, 0),
+  (255, 255, 0),
+  (92, 92, 255),
+  (255, 0, 255),
+  (0, 255, 255),
+  (255, 255, 255),
+];
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+  let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+  d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+fn nearest_cube_level(value: u8) -> usize {
+  (0..CUBE_LEVELS.len())
+    .min_by_key(|index| (CUBE_LEVELS[*index] as i32 - value as i32).abs())
+    .unwrap_or(0)
+}
+
+impl Color {
+  pub fn to_rgb(self) -> (u8, u8, u8) {
+    match self {
+      Color::Rgb(r, g, b) => (r, g, b),
+      Color::Indexed(index @ 0..16) => ANSI_16[index as usize],
+      Color::Indexed(index @ 16..232) => {
+        let index = index - 16;
+        (
+          CUBE_LEVELS[(index / 36) as usize],
+          CUBE_LEVELS[((index / 6) % 6) as usize],
+          CUBE_LEVELS[(index % 6) as usize],
+        )
+      }
+      Color::Indexed(index) => {
+        let level = 8 + (index - 232) * 10;
+        (level, level, level)
+      }
+    }
+  }
+
+  // The closest colour in the 256 colour palette, picking between the colour cube and the
+  // greyscale ramp
+  pub fn to_ansi256(self) -> u8 {
+    let rgb @ (r, g, b) = match self {
+      Color::Indexed(index) => return index,
+      Color::Rgb(r, g, b) => (r, g, b),
+    };
+
+    let (ri, gi, bi) = (
+      nearest_cube_level(r),
+      nearest_cube_level(g),
+      nearest_cube_level(b),
+    );
+    let cube = (16 + 36 * ri + 6 * gi + bi) as u8;
+
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let grey = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+
+    if distance(rgb, Color::Indexed(grey).to_rgb()) < distance(rgb, Color::Indexed(cube).to_rgb()) {
+      grey
+    } else {
+      cube
+    }
+  }
+
+  pub fn to_ansi16(self) -> u8 {
+    if let Color::Indexed(index @ 0..16) = self {
+      return index;
+    }
+
+    let rgb = self.to_rgb();
+    (0..16)
+      .min_by_key(|index| distance(rgb, ANSI_16[*index as usize]))
+      .unwrap_or(0)
+  }
+}
@@ -0,0 +1,310 @@
+// Translates the Vim regexes found in the `vim-match?` predicates of nvim-treesitter queries into
+// the syntax of the regex crate. Vim's "magic" modes decide which characters need a backslash to
+// be special, e.g. `\(a\|b\)\+` by default but `\v(a|b)+` in very magic mode. Only the parts of
+// Vim's syntax with an equivalent in the regex crate are supported.
+
+use regex::bytes::Regex;
+
+#[derive(Debug, thiserror::Error)]
+pub enum VimRegexError {
+  #[error("trailing backslash")]
+  TrailingBackslash,
+  #[error("unsupported {0:?}")]
+  Unsupported(String),
+  #[error("invalid count {0:?}")]
+  InvalidCount(String),
+  #[error(transparent)]
+  Regex(#[from] regex::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct VimRegex {
+  regex: Regex,
+}
+
+impl VimRegex {
+  pub fn new(pattern: &str) -> Result<Self, VimRegexError> {
+    Ok(Self {
+      regex: Regex::new(&translate(pattern)?)?,
+    })
+  }
+
+  // Equivalent to `vim.regex(pattern):match_str(subject) ~= nil`
+  pub fn is_match(&self, subject: &[u8]) -> bool {
+    self.regex.is_match(subject)
+  }
+}
+
+// Every character which can be an operator, with or without a backslash depending on the mode
+const OPERATORS: &str = "()|+?={}<>@%.*[~";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+  VeryMagic,
+  Magic,
+  NoMagic,
+  VeryNoMagic,
+}
+
+impl Mode {
+  // The operators which are special without a backslash, while the rest need one
+  fn unescaped(self) -> &'static str {
+    match self {
+      Mode::VeryMagic => OPERATORS,
+      Mode::Magic => ".*[~",
+      Mode::NoMagic | Mode::VeryNoMagic => "",
+    }
+  }
+
+  fn is_special(self, c: char, escaped: bool) -> bool {
+    OPERATORS.contains(c) && escaped != self.unescaped().contains(c)
+  }
+}
+
+fn class(c: char) -> Option<&'static str> {
+  Some(match c {
+    's' => "[ \\t]",
+    'S' => "[^ \\t]",
+    'd' => "[0-9]",
+    'D' => "[^0-9]",
+    'w' => "[0-9A-Za-z_]",
+    'W' => "[^0-9A-Za-z_]",
+    'a' => "[A-Za-z]",
+    'A' => "[^A-Za-z]",
+    'l' => "[a-z]",
+    'L' => "[^a-z]",
+    'u' => "[A-Z]",
+    'U' => "[^A-Z]",
+    'x' => "[0-9A-Fa-f]",
+    'X' => "[^0-9A-Fa-f]",
+    'o' => "[0-7]",
+    'O' => "[^0-7]",
+    'h' => "[A-Za-z_]",
+    'H' => "[^A-Za-z_]",
+    'n' => "\\n",
+    't' => "\\t",
+    'r' => "\\r",
+    'e' => "\\x1b",
+    _ => return None,
+  })
+}
+
+struct Translator {
+  chars: Vec<char>,
+  position: usize,
+  mode: Mode,
+  output: String,
+}
+
+impl Translator {
+  // The next character and whether it was escaped with a backslash
+  fn next(&mut self) -> Result<Option<(char, bool)>, VimRegexError> {
+    let Some(&c) = self.chars.get(self.position) else {
+      return Ok(None);
+    };
+    self.position += 1;
+    if c != '\\' {
+      return Ok(Some((c, false)));
+    }
+
+    let Some(&c) = self.chars.get(self.position) else {
+      return Err(VimRegexError::TrailingBackslash);
+    };
+    self.position += 1;
+    Ok(Some((c, true)))
+  }
+
+  // Like Vim, `^` is only an anchor at the start of a branch and `$` at the end of one
+  fn at_branch_start(&self) -> bool {
+    self.output.is_empty()
+      || self.output.ends_with('(')
+      || self.output.ends_with("(?:")
+      || self.output.ends_with('|')
+  }
+
+  fn at_branch_end(&self) -> bool {
+    let rest = &self.chars[self.position..];
+    match (self.mode, rest) {
+      (_, []) => true,
+      (Mode::VeryMagic, ['|' | ')', ..]) => true,
+      (Mode::VeryMagic, _) => false,
+      (_, ['\\', '|' | ')', ..]) => true,
+      _ => false,
+    }
+  }
+
+  // `{n,m}` and friends, where a leading `-` makes the count lazy
+  fn count(&mut self) -> Result<(), VimRegexError> {
+    let start = self.position;
+    let Some(end) = self.chars[start..].iter().position(|c| *c == '}') else {
+      return Err(VimRegexError::InvalidCount(
+        self.chars[start..].iter().collect(),
+      ));
+    };
+    self.position = start + end + 1;
+
+    let count = self.chars[start..start + end].iter().collect::<String>();
+    let count = count.strip_suffix('\\').unwrap_or(&count);
+    let (lazy, count) = match count.strip_prefix('-') {
+      Some(count) => (true, count),
+      None => (false, count),
+    };
+
+    let is_number = |value: &str| value.chars().all(|c| c.is_ascii_digit());
+    let quantifier = match count.split_once(',') {
+      None if count.is_empty() => "*".to_string(),
+      None if is_number(count) => format!("{{{count}}}"),
+      Some((min, max)) if is_number(min) && is_number(max) => {
+        let min = if min.is_empty() { "0" } else { min };
+        format!("{{{min},{max}}}")
+      }
+      _ => return Err(VimRegexError::InvalidCount(count.to_string())),
+    };
+
+    self.output.push_str(&quantifier);
+    if lazy {
+      self.output.push('?');
+    }
+    Ok(())
+  }
+
+  // Copies a `[...]` collection, or returns false when it isn't closed and so is a literal `[`
+  fn collection(&mut self) -> bool {
+    let start = self.position;
+    let mut end = start;
+    if self.chars.get(end) == Some(&'^') {
+      end += 1;
+    }
+    // A `]` straight after the opening bracket is part of the collection
+    if self.chars.get(end) == Some(&']') {
+      end += 1;
+    }
+    loop {
+      match self.chars.get(end) {
+        None => return false,
+        Some(']') => break,
+        Some('\\') => end += 2,
+        Some('[') if self.chars.get(end + 1) == Some(&':') => {
+          let class = self.chars[end + 2..]
+            .windows(2)
+            .position(|c| c == [':', ']']);
+          end += class.map_or(1, |class| class + 4);
+        }
+        Some(_) => end += 1,
+      }
+    }
+
+    let mut collection = String::from("[");
+    let mut index = start;
+    if self.chars[index] == '^' {
+      collection.push('^');
+      index += 1;
+    }
+    while index < end {
+      let c = self.chars[index];
+      index += 1;
+      match c {
+        '\\' => match self.chars.get(index) {
+          Some(&escaped @ ('\\' | ']' | '^' | '-')) => {
+            collection.push('\\');
+            collection.push(escaped);
+            index += 1;
+          }
+          Some(&escaped) if matches!(escaped, 'n' | 't' | 'r' | 'e') => {
+            collection.push_str(class(escaped).unwrap_or_default());
+            index += 1;
+          }
+          // Any other backslash is taken literally
+          _ => collection.push_str("\\\\"),
+        },
+        // Character classes such as `[:alpha:]` are shared with the regex crate
+        '[' if self.chars.get(index) == Some(&':') => {
+          collection.push('[');
+          while index < end && !(self.chars[index - 1] == ':' && self.chars[index] == ']') {
+            collection.push(self.chars[index]);
+            index += 1;
+          }
+          if index < end {
+            collection.push(']');
+            index += 1;
+          }
+        }
+        '-' => collection.push('-'),
+        c => collection.push_str(&regex::escape(&c.to_string())),
+      }
+    }
+    collection.push(']');
+
+    self.position = end + 1;
+    self.output.push_str(&collection);
+    true
+  }
+
+  fn operator(&mut self, c: char) -> Result<(), VimRegexError> {
+    match c {
+      '(' => self.output.push('('),
+      ')' => self.output.push(')'),
+      '|' => self.output.push('|'),
+      '+' => self.output.push('+'),
+      '?' | '=' => self.output.push('?'),
+      '*' => self.output.push('*'),
+      '.' => self.output.push('.'),
+      '<' | '>' => self.output.push_str("\\b"),
+      '{' => self.count()?,
+      '[' => {
+        if !self.collection() {
+          self.output.push_str("\\[");
+        }
+      }
+      '%' if self.chars.get(self.position) == Some(&'(') => {
+        self.position += 1;
+        self.output.push_str("(?:");
+      }
+      c => {
+        let rest = self.chars[self.position..].iter().take(2);
+        return Err(VimRegexError::Unsupported(
+          std::iter::once(c).chain(rest.copied()).collect(),
+        ));
+      }
+    }
+    Ok(())
+  }
+}
+
+fn translate(pattern: &str) -> Result<String, VimRegexError> {
+  let mut translator = Translator {
+    chars: pattern.chars().collect(),
+    position: 0,
+    mode: Mode::Magic,
+    output: String::new(),
+  };
+  let mut ignore_case = false;
+
+  while let Some((c, escaped)) = translator.next()? {
+    if translator.mode.is_special(c, escaped) {
+      translator.operator(c)?;
+      continue;
+    }
+
+    match (c, escaped) {
+      ('v', true) => translator.mode = Mode::VeryMagic,
+      ('m', true) => translator.mode = Mode::Magic,
+      ('M', true) => translator.mode = Mode::NoMagic,
+      ('V', true) => translator.mode = Mode::VeryNoMagic,
+      ('c', true) => ignore_case = true,
+      ('C', true) => ignore_case = false,
+      ('^', false) if translator.at_branch_start() => translator.output.push('^'),
+      ('$', false) if translator.at_branch_end() => translator.output.push('$'),
+      (c, true) if c.is_ascii_alphanumeric() => match class(c) {
+        Some(class) => translator.output.push_str(class),
+        None => return Err(VimRegexError::Unsupported(format!("\\{c}"))),
+      },
+      (c, _) => translator.output.push_str(&regex::escape(&c.to_string())),
+    }
+  }
+
+  if ignore_case {
+    translator.output.insert_str(0, "(?i)");
+  }
+  Ok(translator.output)
+}
@@ -0,0 +1,109 @@
+use rehype_tree_sitter_highlight::{
+  AnsiRenderer, Color, ColorMode, HighlightConfiguration, HighlightEvent, Style, Theme, grammar,
+};
+
+fn theme() -> Theme {
+  Theme::from_iter([
+    (
+      "function".to_string(),
+      Style {
+        fg: Some(Color::Rgb(255, 0, 0)),
+        ..Default::default()
+      },
+    ),
+    (
+      "string".to_string(),
+      Style {
+        fg: Some(Color::Indexed(2)),
+        italic: true,
+        ..Default::default()
+      },
+    ),
+    (
+      "number".to_string(),
+      Style {
+        fg: Some(Color::Rgb(0, 135, 255)),
+        bg: Some(Color::Rgb(18, 18, 18)),
+        bold: true,
+        ..Default::default()
+      },
+    ),
+  ])
+}
+
+#[test]
+fn js_ansi() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/injection-language")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  let source = b"f(1, 'a')";
+  let events = highlighter.highlight(source, "javascript")?;
+
+  assert_eq!(
+    AnsiRenderer::new(theme()).render(&events, source),
+    "f(\x1b[1;38;2;0;135;255;48;2;18;18;18m1\x1b[0m, \x1b[3;32m'a'\x1b[0m)"
+  );
+
+  Ok(())
+}
+
+#[test]
+fn color_modes() {
+  let source = b"1";
+  let events = [
+    HighlightEvent::Highlight("number".into()),
+    HighlightEvent::Source { start: 0, end: 1 },
+    HighlightEvent::HighlightEnd,
+  ];
+
+  assert_eq!(
+    AnsiRenderer::new(theme())
+      .color_mode(ColorMode::Ansi256)
+      .render(&events, source),
+    "\x1b[1;38;5;33;48;5;233m1\x1b[0m"
+  );
+  assert_eq!(
+    AnsiRenderer::new(theme())
+      .color_mode(ColorMode::Ansi16)
+      .render(&events, source),
+    "\x1b[1;36;40m1\x1b[0m"
+  );
+}
+
+#[test]
+fn parent_fallback_and_resets() {
+  let source = b"abc\ndef";
+  let events = [
+    HighlightEvent::Highlight("variable".into()),
+    HighlightEvent::Highlight("function.builtin".into()),
+    HighlightEvent::Source { start: 0, end: 5 },
+    HighlightEvent::Highlight("none".into()),
+    HighlightEvent::Source { start: 5, end: 6 },
+    HighlightEvent::HighlightEnd,
+    HighlightEvent::HighlightEnd,
+    HighlightEvent::Source { start: 6, end: 7 },
+    HighlightEvent::HighlightEnd,
+  ];
+
+  // `function.builtin` is styled as `function` and the style is closed at the end of each line,
+  // `none` resets it and `variable` has no style
+  assert_eq!(
+    AnsiRenderer::new(theme()).render(&events, source),
+    "\x1b[38;2;255;0;0mabc\x1b[0m\n\x1b[38;2;255;0;0md\x1b[0mef"
+  );
+}
+
+#[test]
+fn palette_conversion() {
+  assert_eq!(Color::Rgb(255, 0, 0).to_ansi256(), 196);
+  assert_eq!(Color::Rgb(128, 128, 128).to_ansi256(), 244);
+  assert_eq!(Color::Rgb(255, 0, 0).to_ansi16(), 9);
+  assert_eq!(Color::Indexed(196).to_rgb(), (255, 0, 0));
+  assert_eq!(Color::Indexed(232).to_rgb(), (8, 8, 8));
+}
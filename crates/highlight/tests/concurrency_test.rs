@@ -0,0 +1,93 @@
+use rehype_tree_sitter_highlight::{HighlightConfiguration, HighlightError, Highlighter, grammar};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn concurrent_highlighting() -> anyhow::Result<()> {
+  assert_send_sync::<Highlighter>();
+
+  let cwd = std::env::current_dir()?;
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlighter = Highlighter::new(HighlightConfiguration::from_query_paths(&grammars, &[]));
+
+  let sources: [(&[u8], &str); 2] = [
+    (
+      b"let x = `${1 + 2}`;\nfunction f() { return 'a'; }\n",
+      "javascript",
+    ),
+    (
+      b"# Title\n\n```js\nconst y = 2;\n```\n\n*text*\n",
+      "markdown",
+    ),
+  ];
+  let expected = sources
+    .iter()
+    .map(|(source, lang)| highlighter.highlight(source, lang))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let clone = highlighter.clone();
+  std::thread::scope(|scope| {
+    // Half of the threads share the highlighter and half share a clone of it, which shares its
+    // languages and parsers
+    let threads = (0..8)
+      .map(|thread| {
+        let highlighter = if thread % 2 == 0 {
+          &highlighter
+        } else {
+          &clone
+        };
+        let sources = &sources;
+        let expected = &expected;
+        scope.spawn(move || {
+          for _ in 0..20 {
+            for ((source, lang), expected) in sources.iter().zip(expected) {
+              assert_eq!(&highlighter.highlight(source, lang).unwrap(), expected);
+            }
+          }
+        })
+      })
+      .collect::<Vec<_>>();
+
+    for thread in threads {
+      thread.join().unwrap();
+    }
+  });
+
+  Ok(())
+}
+
+#[test]
+fn highlight_many() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlighter = Highlighter::new(HighlightConfiguration::from_query_paths(&grammars, &[]));
+
+  let sources = (0..50)
+    .map(|index| format!("let x{index} = {index};"))
+    .collect::<Vec<_>>();
+  let mut inputs = sources
+    .iter()
+    .map(|source| (source.as_bytes(), "javascript"))
+    .collect::<Vec<_>>();
+  inputs.insert(10, (b"x".as_slice(), "unknown"));
+  inputs.insert(20, (b"# Title\n".as_slice(), "markdown"));
+
+  for workers in [1, 4, 64] {
+    let results = highlighter.clone().workers(workers).highlight_many(&inputs);
+    assert_eq!(results.len(), inputs.len());
+
+    for ((source, lang), result) in inputs.iter().zip(results) {
+      match highlighter.highlight(source, lang) {
+        Ok(expected) => assert_eq!(result?, expected),
+        Err(HighlightError::UnknownLanguage(_)) => {
+          assert!(matches!(result, Err(HighlightError::UnknownLanguage(_))))
+        }
+        Err(err) => return Err(err.into()),
+      }
+    }
+  }
+
+  assert!(highlighter.highlight_many(&[]).is_empty());
+
+  Ok(())
+}
@@ -0,0 +1,104 @@
+use proptest::prelude::*;
+use rehype_tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter, grammar};
+
+fn highlighter() -> anyhow::Result<Highlighter> {
+  highlighter_with_queries(&["../../fixtures/test-queries/injection-language"])
+}
+
+fn highlighter_with_queries(query_paths: &[&str]) -> anyhow::Result<Highlighter> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let query_paths = query_paths
+    .iter()
+    .map(|path| cwd.join(path))
+    .collect::<Vec<_>>();
+  let highlight_configs = HighlightConfiguration::from_query_paths(&grammars, &query_paths);
+
+  Ok(Highlighter::new(highlight_configs))
+}
+
+fn source_ranges(events: &[HighlightEvent]) -> Vec<(usize, usize)> {
+  events
+    .iter()
+    .filter_map(|event| match event {
+      HighlightEvent::Source { start, end } => Some((*start, *end)),
+      _ => None,
+    })
+    .collect()
+}
+
+// The source ranges must tile the input exactly, in order, so that rendering every `Source` event
+// reproduces the input byte for byte.
+fn assert_covers_source(events: &[HighlightEvent], source: &[u8]) {
+  let mut byte = 0;
+  for (start, end) in source_ranges(events) {
+    assert_eq!(start, byte, "gap or overlap in {events:?}");
+    assert!(start < end, "empty or inverted range in {events:?}");
+    byte = end;
+  }
+  assert_eq!(byte, source.len(), "source not covered by {events:?}");
+
+  let starts = events
+    .iter()
+    .filter(|event| matches!(event, HighlightEvent::Highlight(_)))
+    .count();
+  let ends = events
+    .iter()
+    .filter(|event| matches!(event, HighlightEvent::HighlightEnd))
+    .count();
+  assert_eq!(starts, ends, "unbalanced highlights in {events:?}");
+}
+
+#[test]
+fn edge_case_sources() -> anyhow::Result<()> {
+  let highlighter = highlighter()?;
+  // The grammars' own queries, which highlight the closing fence of a code block
+  let bundled = highlighter_with_queries(&[])?;
+
+  for (source, lang) in [
+    ("", "javascript"),
+    ("", "markdown"),
+    ("1", "javascript"),
+    ("\n", "javascript"),
+    (" \n\t \n", "javascript"),
+    ("  ", "markdown"),
+    ("1;", "javascript"),
+    ("1;\n", "javascript"),
+    ("//", "javascript"),
+    ("`1`", "javascript"),
+    ("```\n3\n```", "markdown"),
+    ("```\n3\n```\n", "markdown"),
+    ("```clojure\n(println 1)\n```", "markdown"),
+  ] {
+    for highlighter in [&highlighter, &bundled] {
+      let events = highlighter.highlight(source.as_bytes(), lang)?;
+      assert_covers_source(&events, source.as_bytes());
+    }
+  }
+
+  assert!(highlighter.highlight(b"", "javascript")?.is_empty());
+  assert_eq!(
+    highlighter.highlight(b"x", "javascript")?,
+    &[HighlightEvent::Source { start: 0, end: 1 }]
+  );
+
+  Ok(())
+}
+
+#[test]
+fn source_events_cover_input() -> anyhow::Result<()> {
+  let highlighter = highlighter()?;
+
+  proptest!(|(source in "[a-z0-9 \t\n`'\"/*(){};=.é]{0,48}")| {
+    let events = highlighter.highlight(source.as_bytes(), "javascript").unwrap();
+    assert_covers_source(&events, source.as_bytes());
+  });
+
+  proptest!(|(source in "[a-z0-9 \t\n`#*>-]{0,48}")| {
+    let events = highlighter.highlight(source.as_bytes(), "markdown").unwrap();
+    assert_covers_source(&events, source.as_bytes());
+  });
+
+  Ok(())
+}
@@ -0,0 +1,118 @@
+use rehype_tree_sitter_highlight::{
+  HighlightConfiguration, Highlighter,
+  grammar::{self, LoadOptions},
+};
+
+fn cached_libraries(dir: &std::path::Path) -> anyhow::Result<Vec<String>> {
+  let mut names = std::fs::read_dir(dir)?
+    .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+    .collect::<anyhow::Result<Vec<_>>>()?;
+  names.sort();
+  Ok(names)
+}
+
+#[test]
+fn grammar_cache_dir() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+  let search_paths = [cwd.join("../../fixtures/grammars/")];
+
+  let root = std::env::temp_dir().join(format!("grammar-cache-test-{}", std::process::id()));
+  let cache_dir = root.join("cache");
+  let empty_dir = root.join("empty");
+  std::fs::create_dir_all(&empty_dir)?;
+
+  let options = LoadOptions {
+    cache_dir: Some(cache_dir.clone()),
+    ..Default::default()
+  };
+  let grammars = grammar::load_grammars_with_options(&search_paths, &options)?;
+
+  // Libraries are named by a hash of their sources, and only the finished library is kept
+  let libraries = cached_libraries(&cache_dir)?;
+  let javascript = libraries
+    .iter()
+    .find(|name| name.starts_with("javascript-"))
+    .expect("javascript library in the cache");
+  assert!(javascript.ends_with(std::env::consts::DLL_EXTENSION));
+  assert!(libraries.iter().all(|name| !name.ends_with(".tmp")));
+
+  // A second load reuses the cache rather than compiling again
+  grammar::load_grammars_with_options(&search_paths, &options)?;
+  assert_eq!(cached_libraries(&cache_dir)?, libraries);
+
+  // The cache can be used as a read-only prebuilt cache which must contain every grammar
+  let prebuilt = LoadOptions {
+    cache_dir: Some(empty_dir.clone()),
+    prebuilt_cache_dirs: vec![cache_dir.clone()],
+    require_prebuilt: true,
+    ..Default::default()
+  };
+  let prebuilt_grammars = grammar::load_grammars_with_options(&search_paths, &prebuilt)?;
+  assert_eq!(prebuilt_grammars.len(), grammars.len());
+  assert!(cached_libraries(&empty_dir)?.is_empty());
+
+  let configs = HighlightConfiguration::from_query_paths(&prebuilt_grammars, &[]);
+  let highlighter = Highlighter::new(configs);
+  assert!(
+    !highlighter
+      .highlight(b"let x = 1;", "javascript")?
+      .is_empty()
+  );
+
+  let missing = LoadOptions {
+    cache_dir: Some(empty_dir.clone()),
+    prebuilt_cache_dirs: vec![],
+    require_prebuilt: true,
+    ..Default::default()
+  };
+  assert!(grammar::load_grammars_with_options(&search_paths, &missing).is_err());
+
+  std::fs::remove_dir_all(&root)?;
+  Ok(())
+}
+
+#[test]
+fn concurrent_compiles_share_the_cache() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+  let search_paths = [cwd.join("../../fixtures/grammars/")];
+  let location = grammar::find_grammars(&search_paths)?
+    .into_iter()
+    .find(|location| location.name == "javascript")
+    .expect("javascript grammar");
+
+  let cache_dir = std::env::temp_dir().join(format!(
+    "grammar-cache-concurrent-test-{}",
+    std::process::id()
+  ));
+  let options = LoadOptions {
+    cache_dir: Some(cache_dir.clone()),
+    ..Default::default()
+  };
+
+  std::thread::scope(|scope| {
+    let loads = [(); 2].map(|_| scope.spawn(|| location.load(&options)));
+    for load in loads {
+      load.join().unwrap()?;
+    }
+    anyhow::Ok(())
+  })?;
+
+  // Both threads load the one library, with nothing left behind from the other's compile
+  let libraries = cached_libraries(&cache_dir)?;
+  let dll = std::env::consts::DLL_EXTENSION;
+  assert_eq!(
+    libraries
+      .iter()
+      .filter(|name| name.ends_with(&format!(".{dll}")))
+      .count(),
+    1
+  );
+  assert!(
+    libraries
+      .iter()
+      .all(|name| !name.ends_with(".tmp") && !name.ends_with(".lock"))
+  );
+
+  std::fs::remove_dir_all(&cache_dir)?;
+  Ok(())
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use rehype_tree_sitter_highlight::{
+  ClassNames, HighlightConfiguration, HighlightEvent, HtmlRenderer, grammar,
+};
+
+#[test]
+fn js_html() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/injection-language")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  let source = b"if (a < 1) { '&' }";
+  let events = highlighter.highlight(source, "javascript")?;
+
+  assert_eq!(
+    HtmlRenderer::new().render(&events, source),
+    "if (a &lt; <span class=\"number\">1</span>) { <span class=\"string\">&#39;&amp;&#39;</span> }"
+  );
+
+  Ok(())
+}
+
+fn nested_events() -> Vec<HighlightEvent> {
+  vec![
+    HighlightEvent::Highlight("function.call".into()),
+    HighlightEvent::Source { start: 0, end: 3 },
+    HighlightEvent::Highlight("function.builtin".into()),
+    HighlightEvent::Source { start: 3, end: 6 },
+    HighlightEvent::HighlightEnd,
+    HighlightEvent::HighlightEnd,
+    HighlightEvent::Source { start: 6, end: 7 },
+  ]
+}
+
+#[test]
+fn class_names() {
+  let source = b"foobar\n";
+  let events = nested_events();
+
+  assert_eq!(
+    HtmlRenderer::new()
+      .class_prefix("ts-")
+      .class_names(ClassNames::DotToSpace)
+      .render(&events, source),
+    "<span class=\"ts-function ts-call\">foo</span><span class=\"ts-function ts-builtin\">bar</span>\n"
+  );
+
+  assert_eq!(
+    HtmlRenderer::new()
+      .class_names(ClassNames::DotToDash)
+      .render(&events, source),
+    "<span class=\"function-call\">foo</span><span class=\"function-builtin\">bar</span>\n"
+  );
+}
+
+#[test]
+fn highlight_mapping_and_resets() {
+  let source = b"foobar\n";
+  let events = nested_events();
+
+  // A highlight mapped to `none` resets the enclosing highlights
+  let renderer = HtmlRenderer::new().highlight_mapping(HashMap::from([
+    ("function.builtin".into(), "none".into()),
+    ("function.call".into(), "call".into()),
+  ]));
+  assert_eq!(
+    renderer.render(&events, source),
+    "<span class=\"call\">foo</span>bar\n"
+  );
+}
+
+#[test]
+fn inline_styles() {
+  let source = b"foobar\n";
+  let events = nested_events();
+
+  let renderer = HtmlRenderer::new().inline_styles(HashMap::from([
+    ("function".into(), "color: #00f".into()),
+    (
+      "function.builtin".into(),
+      "color: #f00; font-weight: bold".into(),
+    ),
+  ]));
+  assert_eq!(
+    renderer.render(&events, source),
+    "<span class=\"function.call\" style=\"color: #00f\">foo</span><span class=\"function.builtin\" style=\"color: #f00; font-weight: bold\">bar</span>\n"
+  );
+}
@@ -0,0 +1,56 @@
+use rehype_tree_sitter_highlight::{
+  HighlightConfiguration, HighlightEvent, Highlighter, LoadError, grammar,
+};
+
+fn highlight_names(events: &[HighlightEvent]) -> Vec<&str> {
+  events
+    .iter()
+    .filter_map(|event| match event {
+      HighlightEvent::Highlight(highlight) => Some(highlight.as_str()),
+      _ => None,
+    })
+    .collect()
+}
+
+#[test]
+fn js_inherited_queries() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let (highlight_configs, report) = HighlightConfiguration::from_query_paths_with_report(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/inherits")],
+  );
+  // `ecma` inherits from `(broken)`, which is optional and so skipped when `ecma` is inherited
+  assert!(report.is_ok(), "{report}");
+  let highlighter = Highlighter::new(highlight_configs);
+
+  let events = highlighter.highlight(b"let x = <a>{1}</a> + 'b';", "javascript")?;
+  assert_eq!(
+    highlight_names(&events),
+    &["tag.jsx", "number.ecma", "string.javascript"]
+  );
+
+  Ok(())
+}
+
+#[test]
+fn js_inheritance_cycles() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let (_, report) = HighlightConfiguration::from_query_paths_with_report(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/inherits-cycle")],
+  );
+
+  match &report.languages["javascript"] {
+    Err(LoadError::InheritanceCycle { query, chain, .. }) => {
+      assert_eq!(query, "highlights.scm");
+      assert_eq!(chain, &["javascript", "ecma", "javascript"]);
+    }
+    result => panic!("expected an inheritance cycle, got {result:?}"),
+  }
+
+  Ok(())
+}
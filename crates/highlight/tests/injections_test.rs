@@ -0,0 +1,241 @@
+use rehype_tree_sitter_highlight::{
+  HighlightConfiguration, HighlightError, HighlightEvent, grammar,
+};
+
+fn highlight_names(events: &[HighlightEvent]) -> Vec<&str> {
+  events
+    .iter()
+    .filter_map(|event| match event {
+      HighlightEvent::Highlight(highlight) => Some(highlight.as_str()),
+      _ => None,
+    })
+    .collect()
+}
+
+#[test]
+fn js_combined_injections() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/combined-injections")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  // Neither comment is a valid statement on its own, but together they form an `if` statement
+  let source = b"/* if (x) { */
+y();
+/* } */";
+
+  let events = highlighter.highlight(source, "javascript")?;
+
+  assert_eq!(
+    events,
+    &[
+      HighlightEvent::Highlight("comment".into()),
+      HighlightEvent::Source { start: 0, end: 3 },
+      HighlightEvent::Highlight("keyword.conditional".into()),
+      HighlightEvent::Source { start: 3, end: 7 },
+      HighlightEvent::Highlight("variable".into()),
+      HighlightEvent::Source { start: 7, end: 8 },
+      HighlightEvent::HighlightEnd,
+      HighlightEvent::Source { start: 8, end: 12 },
+      HighlightEvent::HighlightEnd,
+      HighlightEvent::Source { start: 12, end: 14 },
+      HighlightEvent::HighlightEnd,
+      HighlightEvent::Source { start: 14, end: 15 },
+      HighlightEvent::Highlight("variable".into()),
+      HighlightEvent::Source { start: 15, end: 16 },
+      HighlightEvent::HighlightEnd,
+      HighlightEvent::Source { start: 16, end: 20 },
+      HighlightEvent::Highlight("comment".into()),
+      HighlightEvent::Source { start: 20, end: 22 },
+      HighlightEvent::Highlight("keyword.conditional".into()),
+      HighlightEvent::Source { start: 22, end: 24 },
+      HighlightEvent::HighlightEnd,
+      HighlightEvent::Source { start: 24, end: 27 },
+      HighlightEvent::HighlightEnd,
+    ]
+  );
+
+  Ok(())
+}
+
+#[test]
+fn js_combined_injections_per_language() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/combined-injection-language")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  // Both matches come from the same combined pattern, but each is parsed as its own language
+  let source = b"markdown(`# a`); javascript(`1 + x`)";
+
+  let events = highlighter.highlight(source, "javascript")?;
+
+  assert_eq!(
+    highlight_names(&events),
+    &[
+      "variable",
+      "string",
+      "punctuation.special",
+      "text.title",
+      "variable",
+      "string",
+      "number",
+      "variable",
+    ]
+  );
+
+  Ok(())
+}
+
+#[test]
+fn js_injections_exclude_children() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/injection-children")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  // The `some` template only injects the backticks around the interpolation, while `all` sets
+  // `injection.include-children` and so re-parses the interpolated identifier as well.
+  let source = b"some`${a}`; all`${a}`";
+
+  let events = highlighter.highlight(source, "javascript")?;
+
+  assert_eq!(
+    highlight_names(&events),
+    &[
+      "variable", "string", "string", "variable", "string", "variable", "string", "string",
+      "variable", "variable",
+    ]
+  );
+
+  Ok(())
+}
+
+#[test]
+fn js_injection_language_sources() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/injection-language")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  // injection.self
+  let events = highlighter.highlight(b"`1`", "javascript")?;
+  assert_eq!(highlight_names(&events), &["string", "number"]);
+
+  // @injection.filename, resolved through the grammar's file-types
+  let events = highlighter.highlight(b"file(\"x.js\", \"2\")", "javascript")?;
+  assert_eq!(highlight_names(&events), &["string", "string", "number"]);
+
+  // injection.parent, from markdown injected into a javascript comment
+  let source = b"/*
+```
+3
+```
+*/";
+  let events = highlighter.highlight(source, "javascript")?;
+  assert_eq!(
+    highlight_names(&events),
+    &["comment", "text.literal", "number"]
+  );
+
+  Ok(())
+}
+
+#[test]
+fn js_self_injection_terminates() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/self-injection")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  // The comment is the whole source, so injecting it into itself would produce the same layer
+  let events = highlighter.highlight(b"// hi", "javascript")?;
+  assert_eq!(
+    events,
+    &[
+      HighlightEvent::Highlight("comment".into()),
+      HighlightEvent::Source { start: 0, end: 5 },
+      HighlightEvent::HighlightEnd,
+    ]
+  );
+
+  // The comment is injected once, after which its layer is the comment itself
+  let events = highlighter.highlight(b"x; // hi", "javascript")?;
+  assert_eq!(highlight_names(&events), &["comment", "comment"]);
+
+  Ok(())
+}
+
+#[test]
+fn js_offset_columns_are_bytes() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/offsets")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  // The multi-byte character before the comment shifts its column in bytes but not in chars
+  let events = highlighter.highlight("\"é\"; /*12*/".as_bytes(), "javascript")?;
+
+  assert_eq!(
+    events,
+    &[
+      HighlightEvent::Source { start: 0, end: 6 },
+      HighlightEvent::Highlight("comment".into()),
+      HighlightEvent::Source { start: 6, end: 8 },
+      HighlightEvent::Highlight("number".into()),
+      HighlightEvent::Source { start: 8, end: 10 },
+      HighlightEvent::HighlightEnd,
+      HighlightEvent::Source { start: 10, end: 12 },
+      HighlightEvent::HighlightEnd,
+    ]
+  );
+
+  Ok(())
+}
+
+#[test]
+fn js_invalid_offsets() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/invalid-offsets")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  let result = highlighter.highlight(b"/* 1 */", "javascript");
+  assert!(matches!(
+    result,
+    Err(HighlightError::OffsetOutOfBounds { .. })
+  ));
+
+  let result = highlighter.highlight(b"'1'", "javascript");
+  assert!(matches!(result, Err(HighlightError::InvalidOffset { .. })));
+
+  Ok(())
+}
@@ -0,0 +1,77 @@
+use rehype_tree_sitter_highlight::{HighlightError, Highlighter, LoadError, grammar::LoadOptions};
+
+fn cached_grammars(dir: &std::path::Path) -> anyhow::Result<Vec<String>> {
+  if !dir.exists() {
+    return Ok(vec![]);
+  }
+  let mut names = std::fs::read_dir(dir)?
+    .map(|entry| {
+      let name = entry?.file_name().to_string_lossy().to_string();
+      Ok(name.split('-').next().unwrap_or_default().to_string())
+    })
+    .collect::<anyhow::Result<Vec<_>>>()?;
+  names.sort();
+  Ok(names)
+}
+
+#[test]
+fn grammars_load_on_first_use() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+  let cache_dir = std::env::temp_dir().join(format!("lazy-loading-test-{}", std::process::id()));
+
+  let options = LoadOptions {
+    cache_dir: Some(cache_dir.clone()),
+    ..Default::default()
+  };
+  let highlighter =
+    Highlighter::from_grammar_paths(&[cwd.join("../../fixtures/grammars/")], &[], &options)?;
+  assert!(cached_grammars(&cache_dir)?.is_empty());
+
+  assert!(matches!(
+    highlighter.highlight(b"x", "unknown"),
+    Err(HighlightError::UnknownLanguage(_))
+  ));
+  assert!(cached_grammars(&cache_dir)?.is_empty());
+
+  highlighter.highlight(b"let x = 1;", "javascript")?;
+  assert_eq!(cached_grammars(&cache_dir)?, vec!["javascript"]);
+
+  // Injected languages are loaded as they are found
+  highlighter.highlight(b"# Title\n\n*emphasis*\n", "markdown")?;
+  assert_eq!(
+    cached_grammars(&cache_dir)?,
+    vec!["javascript", "markdown", "markdown_inline"]
+  );
+
+  std::fs::remove_dir_all(&cache_dir)?;
+  Ok(())
+}
+
+#[test]
+fn grammar_load_errors() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+  let empty_dir = std::env::temp_dir().join(format!("lazy-loading-empty-{}", std::process::id()));
+
+  // Nothing is compiled when prebuilt grammars are required, so every language fails to load
+  let options = LoadOptions {
+    cache_dir: Some(empty_dir.clone()),
+    prebuilt_cache_dirs: vec![],
+    require_prebuilt: true,
+    ..Default::default()
+  };
+  let highlighter =
+    Highlighter::from_grammar_paths(&[cwd.join("../../fixtures/grammars/")], &[], &options)?;
+
+  for _ in 0..2 {
+    match highlighter.highlight(b"let x = 1;", "js") {
+      Err(HighlightError::Load(LoadError::Grammar { language, reason })) => {
+        assert_eq!(language, "javascript");
+        assert!(reason.contains("prebuilt"), "{reason}");
+      }
+      result => panic!("expected a load error, got {result:?}"),
+    }
+  }
+  assert!(highlighter.capture_names().is_empty());
+
+  Ok(())
+}
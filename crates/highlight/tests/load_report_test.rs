@@ -0,0 +1,144 @@
+use rehype_tree_sitter_highlight::{
+  HighlightConfiguration, Highlighter, LoadError, LoadReport, QueryErrorKind,
+  grammar::{self, LoadOptions},
+};
+
+fn query_error(report: &LoadReport, language: &str) -> rehype_tree_sitter_highlight::QueryError {
+  match &report.languages[language] {
+    Err(LoadError::Query(err)) => err.clone(),
+    result => panic!("expected a query error for {language}, got {result:?}"),
+  }
+}
+
+#[test]
+fn query_errors_are_reported() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+
+  let query_dir = cwd.join("../../fixtures/test-queries/query-errors");
+  let (configs, report) = HighlightConfiguration::from_query_paths_with_report(
+    &grammars,
+    std::slice::from_ref(&query_dir),
+  );
+
+  assert!(!report.is_ok());
+  assert!(!configs.contains_key("javascript"));
+  assert!(configs.contains_key("markdown"));
+  assert!(report.languages["markdown"].is_ok());
+  assert_eq!(report.errors().count(), 1);
+
+  let err = query_error(&report, "javascript");
+  assert_eq!(err.language, "javascript");
+  assert_eq!(err.query, "highlights.scm");
+  assert_eq!(err.path, Some(query_dir.join("javascript/highlights.scm")));
+  assert_eq!((err.row, err.column), (3, 3));
+  assert_eq!(err.kind, QueryErrorKind::NodeType);
+  assert!(
+    report
+      .to_string()
+      .contains("highlights.scm:4:4: NodeType error")
+  );
+
+  Ok(())
+}
+
+#[test]
+fn query_errors_point_at_the_extending_file() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+
+  // The overlay is merged after the grammar's own highlights, but the error is reported on the
+  // line of the overlay it was written on
+  let query_dir = cwd.join("../../fixtures/test-queries/query-errors-extends");
+  let (_, report) = HighlightConfiguration::from_query_paths_with_report(
+    &grammars,
+    std::slice::from_ref(&query_dir),
+  );
+
+  let err = query_error(&report, "javascript");
+  assert_eq!(err.path, Some(query_dir.join("javascript/highlights.scm")));
+  assert_eq!((err.row, err.column), (4, 1));
+  assert_eq!(err.kind, QueryErrorKind::NodeType);
+
+  Ok(())
+}
+
+#[test]
+fn predicate_errors_are_reported() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+
+  let (_, report) = HighlightConfiguration::from_query_paths_with_report(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/invalid-predicate")],
+  );
+
+  let err = query_error(&report, "javascript");
+  assert_eq!(err.kind, QueryErrorKind::Predicate);
+  assert_eq!((err.row, err.column), (0, 0));
+  assert!(err.message.contains("#lua-match?"));
+
+  Ok(())
+}
+
+#[test]
+fn unknown_predicates_are_warnings() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+
+  let query_dir = cwd.join("../../fixtures/test-queries/unknown-predicate");
+  let (configs, report) = HighlightConfiguration::from_query_paths_with_report(
+    &grammars,
+    std::slice::from_ref(&query_dir),
+  );
+
+  assert!(report.is_ok());
+  assert!(configs.contains_key("javascript"));
+
+  let [warning] = report.warnings["javascript"].as_slice() else {
+    panic!("expected a single warning, got {:?}", report.warnings);
+  };
+  assert_eq!(
+    warning.path,
+    Some(query_dir.join("javascript/highlights.scm"))
+  );
+  assert_eq!((warning.row, warning.column), (0, 0));
+  assert_eq!(warning.kind, QueryErrorKind::Predicate);
+  assert!(warning.message.contains("#is-shouty?"));
+
+  Ok(())
+}
+
+#[test]
+fn strict_loading() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+  let search_paths = [cwd.join("../../fixtures/grammars/")];
+  let query_dirs = [cwd.join("../../fixtures/test-queries/query-errors")];
+
+  // Without strict loading, errors are only found once the language is used
+  let highlighter =
+    Highlighter::from_grammar_paths(&search_paths, &query_dirs, &LoadOptions::default())?;
+  let report = highlighter.load_all();
+  assert!(matches!(
+    report.languages["javascript"],
+    Err(LoadError::Query(_))
+  ));
+
+  let strict = LoadOptions {
+    strict: true,
+    ..Default::default()
+  };
+  let Err(err) = Highlighter::from_grammar_paths(&search_paths, &query_dirs, &strict) else {
+    panic!("Expected strict loading to fail");
+  };
+  let report = err.downcast_ref::<LoadReport>().expect("a load report");
+  assert_eq!(report.errors().count(), 1);
+
+  assert!(
+    Highlighter::from_grammar_paths(&search_paths, &[], &strict)?
+      .load_all()
+      .is_ok()
+  );
+
+  Ok(())
+}
@@ -0,0 +1,167 @@
+use rehype_tree_sitter_highlight::{
+  HighlightConfiguration, HighlightEvent, LuaPattern, LuaPatternError, grammar,
+};
+
+#[test]
+fn js_lua_match_predicates() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/lua-match")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  let source = b"/** docs */
+// comment
+MAX_SIZE + size";
+
+  let events = highlighter.highlight(source, "javascript")?;
+
+  assert_eq!(
+    events,
+    &[
+      HighlightEvent::Highlight("comment.documentation".into()),
+      HighlightEvent::Source { start: 0, end: 11 },
+      HighlightEvent::HighlightEnd,
+      HighlightEvent::Source { start: 11, end: 12 },
+      HighlightEvent::Highlight("comment".into()),
+      HighlightEvent::Source { start: 12, end: 22 },
+      HighlightEvent::HighlightEnd,
+      HighlightEvent::Source { start: 22, end: 23 },
+      HighlightEvent::Highlight("constant".into()),
+      HighlightEvent::Source { start: 23, end: 31 },
+      HighlightEvent::HighlightEnd,
+      HighlightEvent::Source { start: 31, end: 34 },
+      HighlightEvent::Highlight("variable".into()),
+      HighlightEvent::Source { start: 34, end: 38 },
+      HighlightEvent::HighlightEnd,
+    ]
+  );
+
+  Ok(())
+}
+
+#[test]
+fn clojure_lua_match_predicates() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs =
+    HighlightConfiguration::from_query_paths(&grammars, &[cwd.join("../../fixtures/queries")]);
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  let source = b"(.toString String/valueOf)";
+  let events = highlighter.highlight(source, "clojure")?;
+
+  let highlights = events
+    .iter()
+    .filter_map(|event| match event {
+      HighlightEvent::Highlight(highlight) => Some(highlight.as_str()),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+
+  assert!(highlights.contains(&"function.method"));
+  assert!(highlights.contains(&"variable.member"));
+
+  Ok(())
+}
+
+#[test]
+fn lua_patterns() -> anyhow::Result<()> {
+  // Pattern, subject and whether `string.find(subject, pattern)` finds a match in Lua
+  let cases: &[(&str, &str, bool)] = &[
+    // Anchors, where `^` and `$` are only special at the start and end of the pattern
+    ("^abc$", "abc", true),
+    ("^abc$", "abcd", false),
+    ("a$", "ba", true),
+    ("a$", "ab", false),
+    ("a$b", "a$b", true),
+    ("^a$b$", "a$b", true),
+    ("a^", "a^", true),
+    // Classes, which follow the C locale and match single bytes
+    ("^%u%l+$", "Hello", true),
+    ("^%x+$", "beef", true),
+    ("^%S+$", "a b", false),
+    ("^%p$", "%", true),
+    ("^..$", "é", true),
+    // Sets
+    ("^[a-c]+$", "abc", true),
+    ("^[a-c]+$", "abd", false),
+    ("^[%d-]+$", "1-2", true),
+    ("^[]]$", "]", true),
+    ("^[]]$", "a", false),
+    ("^[^]]$", "a", true),
+    ("^[^]]$", "]", false),
+    ("^[^%a-]$", "1", true),
+    ("^[^%a-]$", "x", false),
+    ("^[^%a-]$", "-", false),
+    // Repetition, including the lazy `-`
+    ("^ab?c$", "ac", true),
+    ("^a*b$", "b", true),
+    ("^a+b$", "b", false),
+    ("^a-b$", "aaab", true),
+    ("^a-$", "aaa", true),
+    ("^<.->$", "<a><b>", true),
+    ("^%[%[.-%]%]$", "[[x]]", true),
+    ("a-b", "ccc", false),
+    // Balanced matches
+    ("^%b()$", "(a(b)c)", true),
+    ("^%b()$", "(a(b)c", false),
+    ("%b()", "x(y)z", true),
+    ("^%b()", "x(y)", false),
+    // Frontiers, where the start and end of the subject count as `\0`
+    ("%f[%w]foo", "a foo", true),
+    ("%f[%w]foo", "afoo", false),
+    ("^%f[%a]", "a", true),
+    ("foo%f[%A]", "foo", true),
+    ("foo%f[%A]", "foobar", false),
+    ("%f[%a]%a+%f[%A]", "THE (quick) fox", true),
+    ("%f[%a]%a+%f[%A]", "123", false),
+    // Captures and back-references
+    ("^(%a)%1$", "aa", true),
+    ("^(%a)%1$", "ab", false),
+    ("(%a+)=%1", "key=key", true),
+    ("^(%a+)=%1$", "key=kez", false),
+    ("^(a-)%1b$", "aab", true),
+    ("^((%a)%2)%1$", "aaaa", true),
+    ("^((%a)%2)%1$", "aabb", false),
+    // Position captures
+    ("()a", "ba", true),
+    ("^()$", "", true),
+    ("^()a()$", "a", true),
+  ];
+
+  for (pattern, subject, expected) in cases {
+    assert_eq!(
+      LuaPattern::new(pattern)?.is_match(subject.as_bytes()),
+      *expected,
+      "{pattern:?} on {subject:?}"
+    );
+  }
+
+  Ok(())
+}
+
+#[test]
+fn invalid_lua_patterns() {
+  let cases = [
+    ("%", LuaPatternError::TrailingEscape),
+    ("[a", LuaPatternError::MissingBracket),
+    ("[]", LuaPatternError::MissingBracket),
+    ("%b(", LuaPatternError::MissingBalanceArguments),
+    ("%fa", LuaPatternError::MissingFrontierSet),
+    ("(a", LuaPatternError::UnfinishedCapture),
+    ("a)", LuaPatternError::InvalidCapture),
+  ];
+
+  for (pattern, expected) in cases {
+    assert_eq!(
+      LuaPattern::new(pattern).err(),
+      Some(expected),
+      "{pattern:?}"
+    );
+  }
+}
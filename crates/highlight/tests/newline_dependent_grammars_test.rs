@@ -6,13 +6,13 @@ fn markdown_without_newline() -> anyhow::Result<()> {
 
   let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
   let highlight_configs = HighlightConfiguration::from_query_paths(&grammars, &[]);
-  let mut highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
 
   let source = b"```clojure
 (println 1)
 ```";
 
-  let events = highlighter.highlight(source, "markdown");
+  let events = highlighter.highlight(source, "markdown")?;
 
   assert_eq!(
     events,
@@ -29,11 +29,9 @@ fn markdown_without_newline() -> anyhow::Result<()> {
       HighlightEvent::HighlightEnd,
       HighlightEvent::Highlight("punctuation.delimiter".into()),
       HighlightEvent::Highlight("punctuation.special".into()),
-      HighlightEvent::Source { start: 23, end: 23 },
       HighlightEvent::HighlightEnd,
       HighlightEvent::Source { start: 23, end: 26 },
       HighlightEvent::HighlightEnd,
-      HighlightEvent::Source { start: 26, end: 26 },
       HighlightEvent::HighlightEnd
     ]
   );
@@ -48,7 +46,7 @@ fn clojure_with_markdown_no_newline() -> anyhow::Result<()> {
   let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
   let highlight_configs =
     HighlightConfiguration::from_query_paths(&grammars, &[cwd.join("../../fixtures/queries")]);
-  let mut highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
 
   let source = b"(defn some-function 
  \"## This is markdown
@@ -58,7 +56,7 @@ fn clojure_with_markdown_no_newline() -> anyhow::Result<()> {
    ```\"
   [])";
 
-  let events = highlighter.highlight(source, "clojure");
+  let events = highlighter.highlight(source, "clojure")?;
 
   assert_eq!(
     events,
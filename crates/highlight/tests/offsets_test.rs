@@ -0,0 +1,87 @@
+use rehype_tree_sitter_highlight::{
+  HighlightConfiguration, HighlightEvent, Highlighter, OffsetUnit, convert_offsets, grammar,
+};
+
+fn highlighter() -> anyhow::Result<Highlighter> {
+  let cwd = std::env::current_dir()?;
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  Ok(Highlighter::new(HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[],
+  )))
+}
+
+fn source_ranges(events: &[HighlightEvent]) -> Vec<(usize, usize)> {
+  events
+    .iter()
+    .filter_map(|event| match event {
+      HighlightEvent::Source { start, end } => Some((*start, *end)),
+      _ => None,
+    })
+    .collect()
+}
+
+#[test]
+fn js_offset_units() -> anyhow::Result<()> {
+  let highlighter = highlighter()?;
+
+  let source = "let é = '😀 中文'; // ü\nlet x = \"a😀b\";\n";
+  let events = highlighter.highlight(source.as_bytes(), "javascript")?;
+  let byte_ranges = source_ranges(&events);
+
+  let utf16 = source.encode_utf16().collect::<Vec<_>>();
+  let chars = source.chars().collect::<Vec<_>>();
+
+  let utf16_events = convert_offsets(events.clone(), source.as_bytes(), OffsetUnit::Utf16);
+  let char_events = convert_offsets(events.clone(), source.as_bytes(), OffsetUnit::ScalarValues);
+
+  // Every range covers the same text in each unit
+  for (((start, end), (utf16_start, utf16_end)), (char_start, char_end)) in byte_ranges
+    .iter()
+    .zip(source_ranges(&utf16_events))
+    .zip(source_ranges(&char_events))
+  {
+    let text = &source[*start..*end];
+    assert_eq!(String::from_utf16(&utf16[utf16_start..utf16_end])?, text);
+    assert_eq!(chars[char_start..char_end].iter().collect::<String>(), text);
+  }
+
+  // Only the offsets change
+  assert_eq!(utf16_events.len(), events.len());
+  assert_eq!(
+    convert_offsets(events.clone(), source.as_bytes(), OffsetUnit::Bytes),
+    events
+  );
+
+  let end = |events: &[HighlightEvent]| source_ranges(events).last().unwrap().1;
+  assert_eq!(end(&events), source.len());
+  assert_eq!(end(&utf16_events), utf16.len());
+  assert_eq!(end(&char_events), chars.len());
+
+  Ok(())
+}
+
+#[test]
+fn offsets_within_characters() {
+  // A range which splits a character counts it at its start
+  let source = "a😀b".as_bytes();
+  let events = vec![
+    HighlightEvent::Source { start: 0, end: 3 },
+    HighlightEvent::Source { start: 3, end: 6 },
+  ];
+
+  assert_eq!(
+    convert_offsets(events.clone(), source, OffsetUnit::Utf16),
+    &[
+      HighlightEvent::Source { start: 0, end: 3 },
+      HighlightEvent::Source { start: 3, end: 4 },
+    ]
+  );
+  assert_eq!(
+    convert_offsets(events, source, OffsetUnit::ScalarValues),
+    &[
+      HighlightEvent::Source { start: 0, end: 2 },
+      HighlightEvent::Source { start: 2, end: 3 },
+    ]
+  );
+}
@@ -1,4 +1,4 @@
-use rehype_tree_sitter_highlight::{HighlightConfiguration, grammar};
+use rehype_tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, grammar};
 
 #[test]
 fn js_highlight_ordering() -> anyhow::Result<()> {
@@ -6,11 +6,11 @@ fn js_highlight_ordering() -> anyhow::Result<()> {
 
   let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
   let highlight_configs = HighlightConfiguration::from_query_paths(&grammars, &[]);
-  let mut highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
 
   let source = b"console.log(\"content\")";
 
-  let events = highlighter.highlight(source, "javascript");
+  let events = highlighter.highlight(source, "javascript")?;
 
   let highlights = events
     .iter()
@@ -43,11 +43,11 @@ fn js_object_property_ordering() -> anyhow::Result<()> {
 
   let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
   let highlight_configs = HighlightConfiguration::from_query_paths(&grammars, &[]);
-  let mut highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
 
   let source = b"console.log({a: 1})";
 
-  let events = highlighter.highlight(source, "javascript");
+  let events = highlighter.highlight(source, "javascript")?;
 
   let highlights = events
     .iter()
@@ -86,10 +86,10 @@ fn clojure_highlight_ordering() -> anyhow::Result<()> {
     &grammars,
     &[cwd.join("../../fixtures/nvim-treesitter/queries")],
   );
-  let mut highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
 
   let source = b"(sum 1 22)";
-  let events = highlighter.highlight(source, "clojure");
+  let events = highlighter.highlight(source, "clojure")?;
 
   let highlights = events
     .iter()
@@ -113,3 +113,66 @@ fn clojure_highlight_ordering() -> anyhow::Result<()> {
 
   Ok(())
 }
+
+#[test]
+fn js_priority_ordering() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/priority")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  let source = b"x; (1); 2";
+  let events = highlighter.highlight(source, "javascript")?;
+
+  assert_eq!(
+    events,
+    &[
+      HighlightEvent::Highlight("constant".into()),
+      HighlightEvent::Highlight("variable".into()),
+      HighlightEvent::Highlight("variable.special".into()),
+      HighlightEvent::Source { start: 0, end: 1 },
+      HighlightEvent::HighlightEnd,
+      HighlightEvent::HighlightEnd,
+      HighlightEvent::HighlightEnd,
+      HighlightEvent::Source { start: 1, end: 3 },
+      HighlightEvent::Highlight("comment".into()),
+      HighlightEvent::Source { start: 3, end: 6 },
+      HighlightEvent::HighlightEnd,
+      HighlightEvent::Source { start: 6, end: 8 },
+      HighlightEvent::Highlight("number".into()),
+      HighlightEvent::Source { start: 8, end: 9 },
+      HighlightEvent::HighlightEnd,
+    ]
+  );
+
+  Ok(())
+}
+
+#[test]
+fn clojure_priority_ordering() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs =
+    HighlightConfiguration::from_query_paths(&grammars, &[cwd.join("../../fixtures/queries")]);
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  let source = b"#_(sum 1 22)";
+  let events = highlighter.highlight(source, "clojure")?;
+
+  let highlights = events
+    .iter()
+    .filter_map(|event| match event {
+      HighlightEvent::Highlight(highlight) => Some(highlight),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+
+  assert_eq!(highlights, &["comment"]);
+
+  Ok(())
+}
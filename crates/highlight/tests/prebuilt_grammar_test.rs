@@ -0,0 +1,89 @@
+use rehype_tree_sitter_highlight::{
+  HighlightConfiguration, Highlighter,
+  grammar::{self, LoadOptions, LoadedGrammar},
+};
+use std::path::Path;
+
+fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+  std::fs::create_dir_all(to)?;
+  for entry in std::fs::read_dir(from)? {
+    let entry = entry?;
+    if entry.file_type()?.is_dir() {
+      copy_dir(&entry.path(), &to.join(entry.file_name()))?;
+    } else {
+      std::fs::copy(entry.path(), to.join(entry.file_name()))?;
+    }
+  }
+  Ok(())
+}
+
+#[test]
+fn prebuilt_grammar_directory() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+  let javascript = cwd.join("../../fixtures/grammars/javascript");
+
+  let root = std::env::temp_dir().join(format!("prebuilt-grammar-test-{}", std::process::id()));
+  let cache_dir = root.join("cache");
+  let grammars_dir = root.join("grammars");
+
+  let options = LoadOptions {
+    cache_dir: Some(cache_dir.clone()),
+    ..Default::default()
+  };
+  grammar::load_grammars_with_options(&[cwd.join("../../fixtures/grammars/")], &options)?;
+  let library = std::fs::read_dir(&cache_dir)?
+    .map(|entry| entry.unwrap().path())
+    .find(|path| {
+      path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with("javascript-"))
+    })
+    .expect("javascript library in the cache");
+
+  // A grammar directory with a library in place of the grammar's sources
+  let grammar_dir = grammars_dir.join("javascript");
+  copy_dir(&javascript.join("queries"), &grammar_dir.join("queries"))?;
+  std::fs::copy(
+    javascript.join("tree-sitter.json"),
+    grammar_dir.join("tree-sitter.json"),
+  )?;
+  let library_name = format!(
+    "libtree-sitter-javascript.{}",
+    std::env::consts::DLL_EXTENSION
+  );
+  std::fs::copy(&library, grammar_dir.join(&library_name))?;
+
+  let grammars = grammar::load_grammars(std::slice::from_ref(&grammars_dir))?;
+  assert_eq!(grammars.len(), 1);
+  assert!(!grammars["javascript"].highlights.is_empty());
+
+  let configs = HighlightConfiguration::from_query_paths(&grammars, &[]);
+  let highlighter = Highlighter::new(configs);
+  let events = highlighter.highlight(b"let x = 1;", "javascript")?;
+  assert!(
+    events.contains(&rehype_tree_sitter_highlight::HighlightEvent::Highlight(
+      "keyword".to_string()
+    ))
+  );
+
+  let grammar = LoadedGrammar::from_library("javascript", &grammar_dir.join(&library_name))?;
+  assert_eq!(grammar.name, "javascript");
+  assert!(grammar.highlights.is_empty());
+  assert!(LoadedGrammar::from_library("typescript", &grammar_dir.join(&library_name)).is_err());
+
+  // Without a library, a wasm grammar is used instead of compiling the grammar's sources
+  std::fs::remove_file(grammar_dir.join(&library_name))?;
+  std::fs::write(grammar_dir.join("tree-sitter-javascript.wasm"), b"")?;
+  let Err(err) = grammar::load_grammars(std::slice::from_ref(&grammars_dir)) else {
+    panic!("Expected an empty wasm grammar to fail to load");
+  };
+  let message = format!("{err:#}");
+  assert!(message.contains("tree-sitter-javascript.wasm"), "{message}");
+  #[cfg(not(feature = "wasm"))]
+  assert!(message.contains("requires the `wasm` feature"), "{message}");
+  #[cfg(feature = "wasm")]
+  assert!(message.contains("Failed to load wasm grammar"), "{message}");
+
+  std::fs::remove_dir_all(&root)?;
+  Ok(())
+}
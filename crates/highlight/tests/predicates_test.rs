@@ -0,0 +1,109 @@
+use rehype_tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, grammar};
+
+fn highlight_names(events: &[HighlightEvent]) -> Vec<&str> {
+  events
+    .iter()
+    .filter_map(|event| match event {
+      HighlightEvent::Highlight(highlight) => Some(highlight.as_str()),
+      _ => None,
+    })
+    .collect()
+}
+
+#[test]
+fn js_general_predicates() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/predicates")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  let source = b"// TODO: fix
+// plain
+function f(a) { return require(a.b) }";
+
+  let events = highlighter.highlight(source, "javascript")?;
+
+  assert_eq!(
+    highlight_names(&events),
+    &[
+      "comment.todo",
+      "comment",
+      "variable",
+      "variable.parameter",
+      "function.builtin",
+      "variable.member",
+    ]
+  );
+
+  Ok(())
+}
+
+#[test]
+fn js_injection_predicates() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/predicates")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  let source = b"`1 + 2`; `3`";
+
+  let events = highlighter.highlight(source, "javascript")?;
+
+  assert_eq!(
+    highlight_names(&events),
+    &["string", "number", "number", "string"]
+  );
+
+  Ok(())
+}
+
+#[test]
+fn js_vim_match_predicates() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/predicates")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  let events = highlighter.highlight(b"MAX_SIZE + Max", "javascript")?;
+
+  assert_eq!(
+    highlight_names(&events),
+    &["constant", "variable", "variable"]
+  );
+
+  Ok(())
+}
+
+#[test]
+fn unknown_predicates_only_disable_their_pattern() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let grammar = grammars.get("javascript").expect("javascript grammar");
+
+  let config = rehype_tree_sitter_highlight::load_highlight_config(
+    grammar,
+    &[cwd.join("../../fixtures/test-queries/unknown-predicate")],
+  )?;
+  assert_eq!(config.warnings.len(), 1);
+  assert!(config.warnings[0].message.contains("#is-shouty?"));
+
+  let highlighter =
+    rehype_tree_sitter_highlight::Highlighter::new([("javascript".to_string(), config)].into());
+  let events = highlighter.highlight(b"X + 1", "javascript")?;
+  assert_eq!(highlight_names(&events), &["number"]);
+
+  Ok(())
+}
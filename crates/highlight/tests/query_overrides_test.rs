@@ -0,0 +1,80 @@
+use rehype_tree_sitter_highlight::{HighlightEvent, Highlighter, grammar::LoadOptions};
+
+fn highlight_names(events: &[HighlightEvent]) -> Vec<&str> {
+  events
+    .iter()
+    .filter_map(|event| match event {
+      HighlightEvent::Highlight(highlight) => Some(highlight.as_str()),
+      _ => None,
+    })
+    .collect()
+}
+
+#[test]
+fn per_call_query_overrides() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+  let highlighter = Highlighter::from_grammar_paths(
+    &[cwd.join("../../fixtures/grammars/")],
+    &[],
+    &LoadOptions::default(),
+  )?;
+
+  let query_dir = std::env::temp_dir().join(format!("query-overrides-test-{}", std::process::id()));
+  let query_path = query_dir.join("javascript/highlights.scm");
+  std::fs::create_dir_all(query_path.parent().unwrap())?;
+  std::fs::write(&query_path, "(identifier) @first")?;
+
+  let source = b"let x = 1;";
+  let overrides = [query_dir.clone()];
+
+  let events = highlighter.highlight_with_queries(source, "javascript", &overrides)?;
+  assert_eq!(highlight_names(&events), &["first"]);
+  assert_eq!(highlighter.cached_query_overrides(), 1);
+
+  // An identical call reuses the compiled queries
+  let events = highlighter.highlight_with_queries(source, "javascript", &overrides)?;
+  assert_eq!(highlight_names(&events), &["first"]);
+  assert_eq!(highlighter.cached_query_overrides(), 1);
+
+  // The overrides only apply to the call they are passed to
+  let events = highlighter.highlight(source, "javascript")?;
+  assert!(!highlight_names(&events).contains(&"first"));
+
+  // Changes to the query files are picked up by the next call
+  std::fs::write(&query_path, ";; extends\n\n(identifier) @second")?;
+  let events = highlighter.highlight_with_queries(source, "javascript", &overrides)?;
+  let names = highlight_names(&events);
+  assert!(names.contains(&"second"));
+  assert!(names.contains(&"keyword"));
+  assert_eq!(highlighter.cached_query_overrides(), 2);
+
+  // Changing the files back reuses the queries compiled for their earlier contents
+  std::fs::write(&query_path, "(identifier) @first")?;
+  let events = highlighter.highlight_with_queries(source, "javascript", &overrides)?;
+  assert_eq!(highlight_names(&events), &["first"]);
+  assert_eq!(highlighter.cached_query_overrides(), 2);
+
+  // Injected languages use the overrides too, while markdown, which they leave unchanged, uses
+  // the highlighter's own configuration without caching another
+  let events =
+    highlighter.highlight_with_queries(b"```js\nlet x = 1;\n```\n", "markdown", &overrides)?;
+  assert!(highlight_names(&events).contains(&"first"));
+  assert_eq!(highlighter.cached_query_overrides(), 2);
+
+  // Only the most recently used queries are kept
+  for index in 0..40 {
+    std::fs::write(&query_path, format!("(identifier) @name{index}"))?;
+    highlighter.highlight_with_queries(source, "javascript", &overrides)?;
+  }
+  assert_eq!(highlighter.cached_query_overrides(), 32);
+
+  std::fs::write(&query_path, "(not_a_node) @first")?;
+  assert!(
+    highlighter
+      .highlight_with_queries(source, "javascript", &overrides)
+      .is_err()
+  );
+
+  std::fs::remove_dir_all(&query_dir)?;
+  Ok(())
+}
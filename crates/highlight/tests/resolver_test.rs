@@ -0,0 +1,97 @@
+use rehype_tree_sitter_highlight::{
+  HighlightConfiguration, HighlightError, HighlightEvent, Highlighter, LanguageResolver, grammar,
+};
+
+fn highlight_names(events: &[HighlightEvent]) -> Vec<&str> {
+  events
+    .iter()
+    .filter_map(|event| match event {
+      HighlightEvent::Highlight(highlight) => Some(highlight.as_str()),
+      _ => None,
+    })
+    .collect()
+}
+
+fn highlighter(resolver: LanguageResolver) -> anyhow::Result<Highlighter> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/language-resolver")],
+  );
+
+  Ok(Highlighter::with_resolver(highlight_configs, resolver))
+}
+
+#[test]
+fn js_language_names() -> anyhow::Result<()> {
+  let highlighter = highlighter(LanguageResolver::new())?;
+
+  let source = b"let x = 1;\n";
+  let expected = highlighter.highlight(source, "javascript")?;
+  assert!(!expected.is_empty());
+
+  // Injection regex, case variants, file types and filenames all resolve to the same grammar
+  for name in ["js", "JavaScript", "JS", "mjs", "foo.js"] {
+    assert_eq!(highlighter.highlight(source, name)?, expected, "{name}");
+  }
+
+  Ok(())
+}
+
+#[test]
+fn js_aliases() -> anyhow::Result<()> {
+  let mut resolver = LanguageResolver::new();
+  resolver.alias("ecmascript", "javascript");
+  let highlighter = highlighter(resolver)?;
+
+  let source = b"let x = 1;\n";
+  let expected = highlighter.highlight(source, "javascript")?;
+
+  assert_eq!(highlighter.highlight(source, "ECMAScript")?, expected);
+
+  Ok(())
+}
+
+#[test]
+fn unknown_language() -> anyhow::Result<()> {
+  let highlighter = highlighter(LanguageResolver::new())?;
+
+  let result = highlighter.highlight(b"let x = 1;\n", "cobol");
+  assert!(matches!(
+    result,
+    Err(HighlightError::UnknownLanguage(lang)) if lang == "cobol"
+  ));
+
+  Ok(())
+}
+
+#[test]
+fn markdown_info_string_aliases() -> anyhow::Result<()> {
+  let highlighter = highlighter(LanguageResolver::new())?;
+
+  for info_string in ["js", "JavaScript", "javascript"] {
+    let source = format!("```{info_string}\nlet x = 1;\n```\n");
+    let events = highlighter.highlight(source.as_bytes(), "markdown")?;
+
+    assert_eq!(
+      highlight_names(&events),
+      &["markup.raw.block", "number"],
+      "{info_string}"
+    );
+  }
+
+  Ok(())
+}
+
+#[test]
+fn markdown_unknown_injected_language() -> anyhow::Result<()> {
+  let highlighter = highlighter(LanguageResolver::new())?;
+
+  // Only the top-level language is required to be loaded
+  let events = highlighter.highlight(b"```cobol\nx\n```\n", "markdown")?;
+  assert_eq!(highlight_names(&events), &["markup.raw.block"]);
+
+  Ok(())
+}
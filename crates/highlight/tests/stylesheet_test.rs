@@ -0,0 +1,87 @@
+use rehype_tree_sitter_highlight::{
+  ClassNames, HighlightConfiguration, HtmlRenderer, StylesheetScope, Theme, grammar,
+};
+
+#[test]
+fn capture_names() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/injection-language")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  let capture_names = highlighter.capture_names();
+
+  for name in ["comment", "number", "string", "text.literal"] {
+    assert!(
+      capture_names.iter().any(|capture| capture == name),
+      "{name}"
+    );
+  }
+  // Captures such as `@_fn` in the injection queries are not highlights
+  assert!(capture_names.iter().all(|name| !name.starts_with('_')));
+  assert!(capture_names.windows(2).all(|pair| pair[0] < pair[1]));
+
+  Ok(())
+}
+
+#[test]
+fn light_and_dark_stylesheets() -> anyhow::Result<()> {
+  let light = Theme::from_toml(
+    r##"
+    text = "#333333"
+    string = "#008800"
+    "##,
+  )?;
+  let dark = Theme::from_toml(
+    r##"
+    text = { fg = "#eeeeee", italic = true }
+    string = "#88ff88"
+    "##,
+  )?;
+
+  let capture_names = ["string.special.symbol", "text.literal", "number", "string"]
+    .map(String::from)
+    .to_vec();
+  let renderer = HtmlRenderer::new().class_names(ClassNames::DotToDash);
+
+  // Every capture gets a rule of its own, using the style of its closest parent in the theme
+  assert_eq!(
+    renderer.stylesheet_for_captures(&light, &capture_names, &StylesheetScope::Global),
+    ".string { color: #008800; }
+.string-special-symbol { color: #008800; }
+.text-literal { color: #333333; }
+"
+  );
+
+  assert_eq!(
+    renderer.stylesheet_for_captures(
+      &dark,
+      &capture_names,
+      &StylesheetScope::ColorScheme("dark".into())
+    ),
+    "@media (prefers-color-scheme: dark) {
+  .string { color: #88ff88; }
+  .string-special-symbol { color: #88ff88; }
+  .text-literal { color: #eeeeee; font-style: italic; }
+}
+"
+  );
+
+  assert_eq!(
+    renderer.stylesheet_for_captures(
+      &dark,
+      &capture_names,
+      &StylesheetScope::Class("dark".into())
+    ),
+    ".dark .string { color: #88ff88; }
+.dark .string-special-symbol { color: #88ff88; }
+.dark .text-literal { color: #eeeeee; font-style: italic; }
+"
+  );
+
+  Ok(())
+}
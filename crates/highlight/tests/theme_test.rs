@@ -0,0 +1,186 @@
+use rehype_tree_sitter_highlight::{
+  ClassNames, Color, HighlightConfiguration, HtmlRenderer, Style, Theme, ThemeError, grammar,
+};
+
+const HELIX_THEME: &str = r##"
+inherits = "base16"
+rainbow = ["red", "blue"]
+
+"keyword" = "purple"
+"function" = { fg = "blue", modifiers = ["bold"] }
+"function.builtin" = { fg = "#ff8800", modifiers = ["italic", "crossed_out"] }
+"string" = { fg = "green", bg = "default" }
+"diagnostic.error" = { underline = { color = "red", style = "curl" } }
+"ui.background" = { bg = "bg0" }
+
+[palette]
+purple = "#aa00ff"
+bg0 = "#101010"
+"##;
+
+#[test]
+fn helix_theme() -> anyhow::Result<()> {
+  let theme = Theme::from_helix_toml(HELIX_THEME)?;
+
+  assert_eq!(
+    theme.get("keyword.conditional"),
+    Some(&Style {
+      fg: Some(Color::Rgb(0xaa, 0x00, 0xff)),
+      ..Default::default()
+    })
+  );
+  assert_eq!(
+    theme.get("function.call"),
+    Some(&Style {
+      fg: Some(Color::Indexed(4)),
+      bold: true,
+      ..Default::default()
+    })
+  );
+  assert_eq!(
+    theme.get("function.builtin"),
+    Some(&Style {
+      fg: Some(Color::Rgb(0xff, 0x88, 0x00)),
+      italic: true,
+      strikethrough: true,
+      ..Default::default()
+    })
+  );
+  assert_eq!(
+    theme.get("string"),
+    Some(&Style {
+      fg: Some(Color::Indexed(2)),
+      ..Default::default()
+    })
+  );
+  assert!(
+    theme
+      .get("diagnostic.error")
+      .is_some_and(|style| style.underline)
+  );
+  assert_eq!(
+    theme.get("ui.background").and_then(|style| style.bg),
+    Some(Color::Rgb(0x10, 0x10, 0x10))
+  );
+  assert_eq!(theme.get("variable"), None);
+
+  Ok(())
+}
+
+#[test]
+fn simple_themes() -> anyhow::Result<()> {
+  let json = Theme::from_json(
+    r##"{
+      "@keyword": { "fg": "#f00", "bold": true },
+      "comment": "#888888"
+    }"##,
+  )?;
+  let toml = Theme::from_toml(
+    r##"
+    "@keyword" = { fg = "#f00", bold = true }
+    comment = "#888888"
+    "##,
+  )?;
+
+  for theme in [json, toml] {
+    assert_eq!(
+      theme.get("keyword.return"),
+      Some(&Style {
+        fg: Some(Color::Rgb(255, 0, 0)),
+        bold: true,
+        ..Default::default()
+      })
+    );
+    assert_eq!(
+      theme.get("comment").and_then(|style| style.fg),
+      Some(Color::Rgb(0x88, 0x88, 0x88))
+    );
+  }
+
+  Ok(())
+}
+
+#[test]
+fn invalid_themes() {
+  assert!(matches!(
+    Theme::from_json(r#"{ "keyword": "not-a-colour" }"#),
+    Err(ThemeError::InvalidColor { .. })
+  ));
+  assert!(matches!(
+    Theme::from_json(r##"{ "keyword": { "fg": "#fff", "bold": "yes" } }"##),
+    Err(ThemeError::InvalidStyle { .. })
+  ));
+  assert!(matches!(
+    Theme::from_helix_toml(r#"keyword = { modifiers = ["sparkly"] }"#),
+    Err(ThemeError::InvalidStyle { .. })
+  ));
+  assert!(matches!(Theme::from_json("[]"), Err(ThemeError::NotATable)));
+}
+
+#[test]
+fn js_themed_html() -> anyhow::Result<()> {
+  let cwd = std::env::current_dir()?;
+
+  let grammars = grammar::load_grammars(&[cwd.join("../../fixtures/grammars/")])?;
+  let highlight_configs = HighlightConfiguration::from_query_paths(
+    &grammars,
+    &[cwd.join("../../fixtures/test-queries/injection-language")],
+  );
+  let highlighter = rehype_tree_sitter_highlight::Highlighter::new(highlight_configs);
+
+  let theme = Theme::from_toml(
+    r##"
+    number = { fg = "#0000ff", italic = true }
+    string = "green"
+    "##,
+  )?;
+
+  let source = b"f(1, 'a')";
+  let events = highlighter.highlight(source, "javascript")?;
+
+  assert_eq!(
+    HtmlRenderer::new().theme(&theme).render(&events, source),
+    "f(<span class=\"number\" style=\"color: #0000ff; font-style: italic\">1</span>, <span class=\"string\" style=\"color: #00cd00\">&#39;a&#39;</span>)"
+  );
+
+  Ok(())
+}
+
+#[test]
+fn stylesheets() -> anyhow::Result<()> {
+  let theme = Theme::from_toml(
+    r##"
+    "function" = { fg = "#0000ff" }
+    "function.builtin" = { fg = "#ff0000", bold = true, underline = true }
+    "markup.heading.1" = { bold = true }
+    "##,
+  )?;
+
+  assert_eq!(
+    HtmlRenderer::new().stylesheet(&theme),
+    ".function { color: #0000ff; }
+.function\\.builtin { color: #ff0000; font-weight: bold; text-decoration: underline; }
+.markup\\.heading\\.1 { font-weight: bold; }
+"
+  );
+
+  assert_eq!(
+    HtmlRenderer::new()
+      .class_prefix("hl-")
+      .class_names(ClassNames::DotToSpace)
+      .stylesheet(&theme),
+    ".hl-function { color: #0000ff; }
+.hl-function.hl-builtin { color: #ff0000; font-weight: bold; text-decoration: underline; }
+.hl-markup.hl-heading.hl-1 { font-weight: bold; }
+"
+  );
+
+  assert_eq!(
+    HtmlRenderer::new()
+      .class_names(ClassNames::DotToSpace)
+      .selector("markup.heading.1"),
+    ".markup.heading.\\31 "
+  );
+
+  Ok(())
+}
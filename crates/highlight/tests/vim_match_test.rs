@@ -0,0 +1,106 @@
+use rehype_tree_sitter_highlight::{VimRegex, VimRegexError};
+
+#[test]
+fn vim_regexes() -> anyhow::Result<()> {
+  // Pattern, subject and whether `vim.regex(pattern):match_str(subject)` finds a match in Neovim
+  let cases: &[(&str, &str, bool)] = &[
+    // Constants, as matched by nvim-treesitter's queries
+    ("^[A-Z][A-Z\\d_]*$", "MAX_SIZE", true),
+    ("^[A-Z][A-Z\\d_]*$", "Max", false),
+    ("^_*[A-Z][A-Z\\d_]\\+$", "__FOO_BAR", true),
+    ("^_*[A-Z][A-Z\\d_]\\+$", "_F", false),
+    // Classes don't work inside a collection, where the backslash is taken literally
+    ("^_*[A-Z][A-Z\\d_]\\+$", "FOO1", false),
+    ("^[A-Z][A-Z\\d_]*$", "A\\d", true),
+    // Groups, alternation and quantifiers need a backslash in the default magic mode
+    ("^\\(require\\|import\\)$", "require", true),
+    ("^\\(require\\|import\\)$", "requires", false),
+    ("^(require|import)$", "require", false),
+    ("^(require|import)$", "(require|import)", true),
+    ("^a\\+$", "aaa", true),
+    ("^a+$", "aa", false),
+    ("^a+$", "a+", true),
+    ("^https\\?:", "http://", true),
+    ("^a?$", "a?", true),
+    ("^\\%(a\\|b\\)c$", "bc", true),
+    // Counts, including lazy ones
+    ("^\\d\\{2}$", "12", true),
+    ("^\\d\\{2}$", "123", false),
+    ("^\\d\\{1,2\\}$", "1", true),
+    ("^a\\{-1,}$", "aa", true),
+    ("^a{2}$", "a{2}", true),
+    // Very magic, where operators don't need a backslash and escaping makes them literal
+    ("\\v^(require|import)$", "import", true),
+    ("\\v^a+b?$", "aaa", true),
+    ("\\v^\\(a\\)$", "(a)", true),
+    ("\\v^a{2,}$", "aaa", true),
+    ("\\v<TODO>", "a TODO b", true),
+    // Very nomagic, where only a backslash is special
+    ("\\V.*", ".*", true),
+    ("\\V.*", "ab", false),
+    ("\\V^a.\\.$", "a.b", true),
+    // Word boundaries
+    ("\\<TODO\\>", "a TODO b", true),
+    ("\\<TODO\\>", "TODOS", false),
+    // Classes, which only cover ASCII
+    ("^\\s*\\*", "  * docs", true),
+    ("^\\s*\\*", "docs", false),
+    ("^\\h\\w*$", "_x1", true),
+    ("^\\h\\w*$", "1x", false),
+    ("^\\u\\l\\+$", "Hello", true),
+    ("^\\x\\+$", "beef", true),
+    // Collections
+    ("^[]a]$", "]", true),
+    ("^[^]a]$", "b", true),
+    ("^[^]a]$", "a", false),
+    ("^[[:upper:]]\\+$", "ABC", true),
+    ("^[a\\-z]$", "-", true),
+    ("^[a-z&&]$", "&", true),
+    ("[", "[", true),
+    // Anchors are only special at the start and end of a branch
+    ("a^b", "a^b", true),
+    ("a$b", "a$b", true),
+    ("^a\\|^b", "b", true),
+    ("a$\\|b$", "xa", true),
+    // Case
+    ("\\c^todo$", "TODO", true),
+    ("^todo$", "TODO", false),
+  ];
+
+  for (pattern, subject, expected) in cases {
+    assert_eq!(
+      VimRegex::new(pattern)?.is_match(subject.as_bytes()),
+      *expected,
+      "{pattern:?} on {subject:?}"
+    );
+  }
+
+  Ok(())
+}
+
+#[test]
+fn unsupported_vim_regexes() {
+  // Lookarounds, back-references, match boundaries and the last substitute string have no
+  // equivalent in the regex crate
+  for pattern in [
+    "\\(a\\)\\@=",
+    "\\v(a)@!",
+    "\\(a\\)\\1",
+    "foo\\zsbar",
+    "a~",
+    "a\\",
+    "a\\{x}",
+  ] {
+    assert!(
+      matches!(
+        VimRegex::new(pattern),
+        Err(
+          VimRegexError::Unsupported(_)
+            | VimRegexError::TrailingBackslash
+            | VimRegexError::InvalidCount(_)
+        )
+      ),
+      "{pattern:?}"
+    );
+  }
+}
@@ -1,5 +1,10 @@
+use napi::{
+  Env, Task,
+  bindgen_prelude::{AsyncTask, Either},
+};
 use napi_derive::napi;
-use rehype_tree_sitter_highlight::{HighlightConfiguration, grammar};
+use rehype_tree_sitter_highlight::grammar;
+use std::path::PathBuf;
 
 #[napi]
 pub enum HighlightEventType {
@@ -8,6 +13,25 @@ pub enum HighlightEventType {
   End,
 }
 
+// The unit of the offsets in each `HighlightRange`. JavaScript strings are indexed by UTF-16 code
+// units, while the highlighter works in UTF-8 bytes.
+#[napi]
+pub enum OffsetUnit {
+  Bytes,
+  Utf16,
+  ScalarValues,
+}
+
+impl From<OffsetUnit> for rehype_tree_sitter_highlight::OffsetUnit {
+  fn from(unit: OffsetUnit) -> Self {
+    match unit {
+      OffsetUnit::Bytes => rehype_tree_sitter_highlight::OffsetUnit::Bytes,
+      OffsetUnit::Utf16 => rehype_tree_sitter_highlight::OffsetUnit::Utf16,
+      OffsetUnit::ScalarValues => rehype_tree_sitter_highlight::OffsetUnit::ScalarValues,
+    }
+  }
+}
+
 #[napi(object)]
 pub struct HighlightRange {
   pub start: u32,
@@ -34,15 +58,106 @@ pub struct HighlightParams {
   pub grammar_paths: Option<Vec<String>>,
 }
 
+#[napi(object)]
+pub struct HighlightInput {
+  pub source: String,
+  pub language: String,
+}
+
+#[napi(object)]
+pub struct HighlightFailure {
+  // `InvalidArg` for an unknown language, like the errors thrown by `highlight`
+  pub code: String,
+  pub message: String,
+}
+
+// Either the events or the error of highlighting one input
+#[napi(object)]
+pub struct HighlightResult {
+  pub events: Option<Vec<HighlightEvent>>,
+  pub error: Option<HighlightFailure>,
+}
+
+#[napi(object)]
+pub struct GrammarCacheOptions {
+  #[napi(js_name = "cache_dir")]
+  pub cache_dir: Option<String>,
+
+  #[napi(js_name = "prebuilt_cache_dirs")]
+  pub prebuilt_cache_dirs: Option<Vec<String>>,
+
+  #[napi(js_name = "require_prebuilt")]
+  pub require_prebuilt: Option<bool>,
+}
+
+#[napi(object)]
+#[derive(Default)]
+pub struct HighlighterOptions {
+  #[napi(js_name = "query_paths")]
+  pub query_paths: Option<Vec<String>>,
+
+  #[napi(js_name = "grammar_cache")]
+  pub grammar_cache: Option<GrammarCacheOptions>,
+
+  // Loads every grammar and its queries up front, failing if any of them fail to load
+  pub strict: Option<bool>,
+
+  // Defaults to bytes
+  #[napi(js_name = "offset_unit")]
+  pub offset_unit: Option<OffsetUnit>,
+}
+
+#[napi(object)]
+pub struct QueryError {
+  pub query: String,
+  pub path: Option<String>,
+  // Zero-based
+  pub row: u32,
+  pub column: u32,
+  pub kind: String,
+  pub message: String,
+}
+
+#[napi(object)]
+pub struct LanguageLoadResult {
+  pub language: String,
+  // Unset when the language loaded
+  pub error: Option<String>,
+  #[napi(js_name = "query_error")]
+  pub query_error: Option<QueryError>,
+  // Patterns left out of the queries because they use predicates which can't be evaluated
+  pub warnings: Vec<QueryError>,
+}
+
+// Clones of the highlighter share its languages, so that tasks on the libuv thread pool can
+// highlight concurrently
 #[napi]
 pub struct Highlighter {
   highlighter: rehype_tree_sitter_highlight::Highlighter,
+  offset_unit: rehype_tree_sitter_highlight::OffsetUnit,
 }
 
 #[napi]
 impl Highlighter {
   #[napi(constructor)]
-  pub fn new(grammar_paths: Vec<String>, query_paths: Option<Vec<String>>) -> napi::Result<Self> {
+  pub fn new(
+    grammar_paths: Vec<String>,
+    // Either the options or, as in earlier versions, just the query paths
+    options: Option<Either<Vec<String>, HighlighterOptions>>,
+  ) -> napi::Result<Self> {
+    let HighlighterOptions {
+      query_paths,
+      grammar_cache,
+      strict,
+      offset_unit,
+    } = match options {
+      Some(Either::A(query_paths)) => HighlighterOptions {
+        query_paths: Some(query_paths),
+        ..Default::default()
+      },
+      Some(Either::B(options)) => options,
+      None => HighlighterOptions::default(),
+    };
     let cwd = std::env::current_dir()?;
 
     let search_paths = grammar_paths
@@ -50,56 +165,271 @@ impl Highlighter {
       .map(|dir| cwd.join(dir))
       .collect::<Vec<_>>();
 
-    let query_dirs = query_paths
-      .unwrap_or_default()
-      .iter()
-      .map(|dir| cwd.join(dir))
-      .collect::<Vec<_>>();
+    let query_dirs = resolve_paths(query_paths)?;
 
-    let grammars = grammar::load_grammars(&search_paths)
-      .map_err(|err| napi::Error::from_reason(format!("{err:?}")))?;
+    let mut load_options = match grammar_cache {
+      Some(options) => grammar::LoadOptions {
+        cache_dir: options.cache_dir.map(|dir| cwd.join(dir)),
+        prebuilt_cache_dirs: options
+          .prebuilt_cache_dirs
+          .unwrap_or_default()
+          .iter()
+          .map(|dir| cwd.join(dir))
+          .collect::<Vec<PathBuf>>(),
+        require_prebuilt: options.require_prebuilt.unwrap_or(false),
+        ..Default::default()
+      },
+      None => grammar::LoadOptions::default(),
+    };
+    load_options.strict = strict.unwrap_or(false);
 
-    let highlight_configs = HighlightConfiguration::from_query_paths(&grammars, &query_dirs);
+    // Grammars are compiled the first time they are highlighted, unless loading is strict
+    let highlighter = rehype_tree_sitter_highlight::Highlighter::from_grammar_paths(
+      &search_paths,
+      &query_dirs,
+      &load_options,
+    )
+    .map_err(|err| napi::Error::from_reason(format!("{err:?}")))?;
 
     Ok(Self {
-      highlighter: rehype_tree_sitter_highlight::Highlighter::new(highlight_configs),
+      highlighter,
+      offset_unit: offset_unit.map(Into::into).unwrap_or_default(),
     })
   }
 
+  // Loads every grammar and its queries, reporting any which fail to load
+  #[napi]
+  pub fn load_all(&self) -> napi::Result<Vec<LanguageLoadResult>> {
+    let mut report = self.highlighter.load_all();
+    let results = report
+      .languages
+      .into_iter()
+      .map(|(language, result)| {
+        let error = result.err();
+        let query_error = match &error {
+          Some(rehype_tree_sitter_highlight::LoadError::Query(err)) => Some(to_js_query_error(err)),
+          _ => None,
+        };
+        let warnings = report
+          .warnings
+          .remove(&language)
+          .unwrap_or_default()
+          .iter()
+          .map(to_js_query_error)
+          .collect();
+
+        LanguageLoadResult {
+          language,
+          error: error.map(|err| err.to_string()),
+          query_error,
+          warnings,
+        }
+      })
+      .collect();
+
+    Ok(results)
+  }
+
   #[napi]
   pub fn highlight(
-    &mut self,
+    &self,
     source: String,
     language: String,
+    query_paths: Option<Vec<String>>,
   ) -> napi::Result<Vec<HighlightEvent>> {
-    let source = source.into_bytes();
+    let mut task = HighlightTask {
+      highlighter: self.highlighter.clone(),
+      offset_unit: self.offset_unit,
+      source,
+      language,
+      query_dirs: resolve_paths(query_paths)?,
+    };
 
-    let highlights = self.highlighter.highlight(source.as_slice(), &language);
+    let events = task.compute()?;
+    Ok(to_js_events(events))
+  }
+
+  // Highlights on the libuv thread pool rather than blocking the event loop
+  #[napi(ts_return_type = "Promise<HighlightEvent[]>")]
+  pub fn highlight_async(
+    &self,
+    source: String,
+    language: String,
+    query_paths: Option<Vec<String>>,
+  ) -> napi::Result<AsyncTask<HighlightTask>> {
+    Ok(AsyncTask::new(HighlightTask {
+      highlighter: self.highlighter.clone(),
+      offset_unit: self.offset_unit,
+      source,
+      language,
+      query_dirs: resolve_paths(query_paths)?,
+    }))
+  }
+
+  // Highlights every input off the event loop with `workers` threads, which defaults to the
+  // available parallelism, resolving with the results in the same order as the inputs
+  #[napi(ts_return_type = "Promise<HighlightResult[]>")]
+  pub fn highlight_many(
+    &self,
+    inputs: Vec<HighlightInput>,
+    workers: Option<u32>,
+  ) -> AsyncTask<HighlightManyTask> {
+    let mut highlighter = self.highlighter.clone();
+    if let Some(workers) = workers {
+      highlighter = highlighter.workers(workers as usize);
+    }
+
+    AsyncTask::new(HighlightManyTask {
+      highlighter,
+      offset_unit: self.offset_unit,
+      inputs,
+    })
+  }
+}
 
-    let events = highlights
+fn resolve_paths(paths: Option<Vec<String>>) -> napi::Result<Vec<PathBuf>> {
+  let cwd = std::env::current_dir()?;
+  Ok(
+    paths
+      .unwrap_or_default()
       .iter()
-      .map(|event| match event {
-        rehype_tree_sitter_highlight::HighlightEvent::Highlight(s) => HighlightEvent {
-          event_type: HighlightEventType::Start,
-          highlight: Some(s.clone()),
-          range: None,
-        },
-        rehype_tree_sitter_highlight::HighlightEvent::Source { start, end } => HighlightEvent {
-          event_type: HighlightEventType::Source,
-          highlight: None,
-          range: Some(HighlightRange {
-            start: *start as u32,
-            end: *end as u32,
-          }),
-        },
-        rehype_tree_sitter_highlight::HighlightEvent::HighlightEnd => HighlightEvent {
-          event_type: HighlightEventType::End,
-          highlight: None,
-          range: None,
-        },
-      })
+      .map(|dir| cwd.join(dir))
+      .collect(),
+  )
+}
+
+fn to_js_query_error(err: &rehype_tree_sitter_highlight::QueryError) -> QueryError {
+  QueryError {
+    query: err.query.clone(),
+    path: err
+      .path
+      .as_ref()
+      .map(|path| path.to_string_lossy().to_string()),
+    row: err.row as u32,
+    column: err.column as u32,
+    kind: format!("{:?}", err.kind),
+    message: err.message.clone(),
+  }
+}
+
+fn to_js_events(events: Vec<rehype_tree_sitter_highlight::HighlightEvent>) -> Vec<HighlightEvent> {
+  events
+    .into_iter()
+    .map(|event| match event {
+      rehype_tree_sitter_highlight::HighlightEvent::Highlight(s) => HighlightEvent {
+        event_type: HighlightEventType::Start,
+        highlight: Some(s),
+        range: None,
+      },
+      rehype_tree_sitter_highlight::HighlightEvent::Source { start, end } => HighlightEvent {
+        event_type: HighlightEventType::Source,
+        highlight: None,
+        range: Some(HighlightRange {
+          start: start as u32,
+          end: end as u32,
+        }),
+      },
+      rehype_tree_sitter_highlight::HighlightEvent::HighlightEnd => HighlightEvent {
+        event_type: HighlightEventType::End,
+        highlight: None,
+        range: None,
+      },
+    })
+    .collect()
+}
+
+pub struct HighlightTask {
+  highlighter: rehype_tree_sitter_highlight::Highlighter,
+  offset_unit: rehype_tree_sitter_highlight::OffsetUnit,
+  source: String,
+  language: String,
+  query_dirs: Vec<PathBuf>,
+}
+
+fn to_napi_error(err: rehype_tree_sitter_highlight::HighlightError) -> napi::Error {
+  match err {
+    rehype_tree_sitter_highlight::HighlightError::UnknownLanguage(_) => {
+      napi::Error::new(napi::Status::InvalidArg, err.to_string())
+    }
+    err => napi::Error::from_reason(err.to_string()),
+  }
+}
+
+impl Task for HighlightTask {
+  type Output = Vec<rehype_tree_sitter_highlight::HighlightEvent>;
+  type JsValue = Vec<HighlightEvent>;
+
+  fn compute(&mut self) -> napi::Result<Self::Output> {
+    let source = self.source.as_bytes();
+    let events = self
+      .highlighter
+      .highlight_with_queries(source, &self.language, &self.query_dirs)
+      .map_err(to_napi_error)?;
+
+    Ok(rehype_tree_sitter_highlight::convert_offsets(
+      events,
+      source,
+      self.offset_unit,
+    ))
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> napi::Result<Self::JsValue> {
+    Ok(to_js_events(output))
+  }
+}
+
+pub struct HighlightManyTask {
+  highlighter: rehype_tree_sitter_highlight::Highlighter,
+  offset_unit: rehype_tree_sitter_highlight::OffsetUnit,
+  inputs: Vec<HighlightInput>,
+}
+
+impl Task for HighlightManyTask {
+  type Output = Vec<napi::Result<Vec<rehype_tree_sitter_highlight::HighlightEvent>>>;
+  type JsValue = Vec<HighlightResult>;
+
+  fn compute(&mut self) -> napi::Result<Self::Output> {
+    let inputs = self
+      .inputs
+      .iter()
+      .map(|input| (input.source.as_bytes(), input.language.as_str()))
       .collect::<Vec<_>>();
 
-    Ok(events)
+    Ok(
+      self
+        .highlighter
+        .highlight_many(&inputs)
+        .into_iter()
+        .zip(&inputs)
+        .map(|(result, (source, _))| {
+          result
+            .map(|events| {
+              rehype_tree_sitter_highlight::convert_offsets(events, source, self.offset_unit)
+            })
+            .map_err(to_napi_error)
+        })
+        .collect(),
+    )
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> napi::Result<Self::JsValue> {
+    Ok(
+      output
+        .into_iter()
+        .map(|result| match result {
+          Ok(events) => HighlightResult {
+            events: Some(to_js_events(events)),
+            error: None,
+          },
+          Err(err) => HighlightResult {
+            events: None,
+            error: Some(HighlightFailure {
+              code: err.status.as_ref().to_string(),
+              message: err.reason.clone(),
+            }),
+          },
+        })
+        .collect(),
+    )
   }
 }
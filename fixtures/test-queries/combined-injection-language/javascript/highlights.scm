@@ -0,0 +1,5 @@
+(template_string) @string
+
+(number) @number
+
+(identifier) @variable
@@ -0,0 +1,6 @@
+((call_expression
+  function: (identifier) @injection.language
+  arguments: (arguments
+    (template_string
+      (string_fragment) @injection.content)))
+  (#set! injection.combined))
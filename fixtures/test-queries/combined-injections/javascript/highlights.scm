@@ -0,0 +1,5 @@
+(comment) @comment
+
+(if_statement) @keyword.conditional
+
+(identifier) @variable
@@ -0,0 +1,4 @@
+((comment) @injection.content
+  (#offset! @injection.content 0 2 0 -2)
+  (#set! injection.language "javascript")
+  (#set! injection.combined))
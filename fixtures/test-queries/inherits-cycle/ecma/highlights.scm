@@ -0,0 +1,3 @@
+;; inherits: javascript
+
+(number) @number
@@ -0,0 +1,3 @@
+; inherits: ecma
+
+(string) @string
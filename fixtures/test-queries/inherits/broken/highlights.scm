@@ -0,0 +1 @@
+(not_a_node) @broken
@@ -0,0 +1,3 @@
+; inherits: (broken)
+
+(number) @number.ecma
@@ -0,0 +1,3 @@
+; inherits: ecma,(jsx)
+
+(string) @string.javascript
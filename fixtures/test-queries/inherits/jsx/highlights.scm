@@ -0,0 +1,2 @@
+(jsx_opening_element
+  name: (identifier) @tag.jsx)
@@ -0,0 +1,3 @@
+(template_string) @string
+
+(identifier) @variable
@@ -0,0 +1,12 @@
+((call_expression
+  function: (identifier) @_tag
+  arguments: (template_string) @injection.content)
+  (#eq? @_tag "some")
+  (#set! injection.language "javascript"))
+
+((call_expression
+  function: (identifier) @_tag
+  arguments: (template_string) @injection.content)
+  (#eq? @_tag "all")
+  (#set! injection.language "javascript")
+  (#set! injection.include-children))
@@ -0,0 +1,7 @@
+(number) @number
+
+(comment) @comment
+
+(string) @string
+
+(template_string) @string
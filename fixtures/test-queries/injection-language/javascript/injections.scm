@@ -0,0 +1,16 @@
+((template_string
+  (string_fragment) @injection.content)
+  (#set! injection.self))
+
+((call_expression
+  function: (identifier) @_fn
+  arguments: (arguments
+    (string
+      (string_fragment) @injection.filename)
+    (string
+      (string_fragment) @injection.content)))
+  (#eq? @_fn "file"))
+
+((comment) @injection.content
+  (#offset! @injection.content 0 2 0 -2)
+  (#set! injection.language "markdown"))
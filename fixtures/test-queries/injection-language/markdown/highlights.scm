@@ -0,0 +1 @@
+(fenced_code_block) @text.literal
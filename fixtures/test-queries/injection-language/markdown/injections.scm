@@ -0,0 +1,3 @@
+(fenced_code_block
+  (code_fence_content) @injection.content
+  (#set! injection.parent))
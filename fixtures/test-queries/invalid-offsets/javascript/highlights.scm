@@ -0,0 +1 @@
+(number) @number
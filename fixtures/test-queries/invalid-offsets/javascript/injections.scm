@@ -0,0 +1,7 @@
+((comment) @injection.content
+  (#offset! @injection.content 0 -100 0 0)
+  (#set! injection.language "javascript"))
+
+((string) @injection.content
+  (#offset! @injection.content 0 one 0 0)
+  (#set! injection.language "javascript"))
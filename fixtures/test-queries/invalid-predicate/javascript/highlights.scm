@@ -0,0 +1,2 @@
+((identifier) @constant
+  (#lua-match? @constant))
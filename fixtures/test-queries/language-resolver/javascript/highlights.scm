@@ -0,0 +1,3 @@
+(number) @number
+
+(string) @string
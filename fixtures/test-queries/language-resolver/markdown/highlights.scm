@@ -0,0 +1 @@
+(fenced_code_block) @markup.raw.block
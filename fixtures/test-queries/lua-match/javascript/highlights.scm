@@ -0,0 +1,11 @@
+((identifier) @constant
+  (#lua-match? @constant "^%u[%u%d_]*$"))
+
+((identifier) @variable
+  (#not-lua-match? @variable "^%u[%u%d_]*$"))
+
+((comment) @comment.documentation
+  (#lua-match? @comment.documentation "^/[*][*][^*].*[*]/$"))
+
+((comment) @comment
+  (#not-lua-match? @comment "^/[*][*][^*].*[*]/$"))
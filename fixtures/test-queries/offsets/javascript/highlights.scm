@@ -0,0 +1,3 @@
+(number) @number
+
+(comment) @comment
@@ -0,0 +1,3 @@
+((comment) @injection.content
+  (#offset! @injection.content 0 2 0 -2)
+  (#set! injection.language "javascript"))
@@ -0,0 +1,25 @@
+((identifier) @constant
+  (#vim-match? @constant "^[A-Z][A-Z_0-9]*$"))
+
+(number) @number
+
+(template_string) @string
+
+((identifier) @function.builtin
+  (#any-of? @function.builtin "require" "define"))
+
+((identifier) @variable.parameter
+  (#has-ancestor? @variable.parameter formal_parameters))
+
+((identifier) @variable.member
+  (#has-parent? @variable.member member_expression))
+
+((identifier) @variable
+  (#not-has-parent? @variable member_expression call_expression)
+  (#not-has-ancestor? @variable formal_parameters))
+
+((comment) @comment.todo
+  (#contains? @comment.todo "TODO" "FIXME"))
+
+((comment) @comment
+  (#not-contains? @comment "TODO" "FIXME"))
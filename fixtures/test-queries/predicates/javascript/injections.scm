@@ -0,0 +1,7 @@
+((template_string
+  (string_fragment) @injection.content)
+  (#contains? @injection.content "+")
+  (#set! injection.language "javascript"))
+
+((comment) @injection.content
+  (#set-lang-from-mimetype! @injection.content))
@@ -0,0 +1,12 @@
+((identifier) @variable.special
+  (#set! priority 110))
+
+(identifier) @variable
+
+((identifier) @constant
+  (#set! priority 90))
+
+(number) @number
+
+((parenthesized_expression) @comment
+  (#set! priority 105))
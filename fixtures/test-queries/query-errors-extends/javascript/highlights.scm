@@ -0,0 +1,5 @@
+;; extends
+
+(identifier) @variable
+
+(not_a_node) @keyword
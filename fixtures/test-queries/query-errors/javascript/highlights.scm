@@ -0,0 +1,4 @@
+(identifier) @variable
+
+(string
+  (not_a_node) @string.special)
@@ -0,0 +1,4 @@
+((identifier) @constant
+  (#is-shouty? @constant))
+
+(number) @number
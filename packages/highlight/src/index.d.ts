@@ -4,6 +4,16 @@ export const enum HighlightEventType {
   End,
 }
 
+/**
+ * The unit of the offsets in each `HighlightRange`. Use `Utf16` to slice the
+ * source with `String.prototype.substring`.
+ */
+export const enum OffsetUnit {
+  Bytes,
+  Utf16,
+  ScalarValues,
+}
+
 export type HighlightRange = {
   start: number;
   end: number;
@@ -22,14 +32,120 @@ export type HighlightEvent =
       range: HighlightRange;
     };
 
+export type HighlightInput = {
+  source: string;
+  language: string;
+};
+
+export type HighlightResult =
+  | {
+      events: HighlightEvent[];
+    }
+  | {
+      error: {
+        /** `InvalidArg` for an unknown language, like `highlight` */
+        code: string;
+        message: string;
+      };
+    };
+
+export type GrammarCacheOptions = {
+  cache_dir?: string;
+  prebuilt_cache_dirs?: string[];
+  require_prebuilt?: boolean;
+};
+
+export type QueryError = {
+  query: string;
+  path?: string;
+  /** Zero-based */
+  row: number;
+  /** Zero-based */
+  column: number;
+  kind:
+    | "Syntax"
+    | "NodeType"
+    | "Field"
+    | "Capture"
+    | "Predicate"
+    | "Structure"
+    | "Language";
+  message: string;
+};
+
+export type LanguageLoadResult = {
+  language: string;
+  /** Unset when the language loaded */
+  error?: string;
+  query_error?: QueryError;
+  /**
+   * Patterns left out of the queries because they use predicates which can't
+   * be evaluated
+   */
+  warnings: QueryError[];
+};
+
+export type HighlighterOptions = {
+  query_paths?: string[];
+  grammar_cache?: GrammarCacheOptions;
+  /**
+   * Load every grammar and its queries immediately, throwing from the
+   * constructor if any of them fail to load.
+   */
+  strict?: boolean;
+  /** The unit of each `HighlightRange`, which defaults to UTF-8 bytes */
+  offset_unit?: OffsetUnit;
+};
+
 export class Highlighter {
+  /**
+   * Grammars are found up front, but are only compiled and loaded the first
+   * time their language is highlighted, unless loading is `strict`.
+   */
+  constructor(grammar_paths: string[], options?: HighlighterOptions);
   constructor(grammar_paths: string[], query_paths?: string[]);
-  highlight(source: String, language: String): HighlightEvent[];
+  /**
+   * Loads every grammar and its queries, reporting any which fail to load.
+   */
+  loadAll(): LanguageLoadResult[];
+  /**
+   * Throws if the source can't be highlighted, including when its grammar
+   * fails to load. An unknown language throws an error with the `InvalidArg`
+   * code.
+   *
+   * `query_paths` are used on top of the highlighter's own query paths for
+   * this call only. Their queries are compiled once and reused by later calls.
+   */
+  highlight(
+    source: String,
+    language: String,
+    query_paths?: string[],
+  ): HighlightEvent[];
+  /**
+   * Like `highlight`, but highlights on the libuv thread pool instead of
+   * blocking the event loop. Rejects where `highlight` would throw.
+   */
+  highlightAsync(
+    source: String,
+    language: String,
+    query_paths?: string[],
+  ): Promise<HighlightEvent[]>;
+  /**
+   * Highlights every input off the event loop with `workers` threads, which
+   * defaults to the available parallelism. Results are in the same order as
+   * the inputs, with an error in place of the events for any input which
+   * couldn't be highlighted.
+   */
+  highlightMany(
+    inputs: HighlightInput[],
+    workers?: number,
+  ): Promise<HighlightResult[]>;
 }
 
 declare const tree_sitter_highlight: {
   Highlighter: typeof Highlighter;
   HighlightEventType: typeof HighlightEventType;
+  OffsetUnit: typeof OffsetUnit;
 };
 
 export default tree_sitter_highlight;
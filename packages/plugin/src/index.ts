@@ -1,4 +1,8 @@
 import highlight from "@julienvincent/tree-sitter-highlight";
+import type {
+  GrammarCacheOptions,
+  HighlightEvent,
+} from "@julienvincent/tree-sitter-highlight";
 import { visit } from "unist-util-visit";
 import type { Element, ElementContent } from "hast";
 
@@ -9,6 +13,12 @@ export type HighlighterOptions = {
   highlight_mapping?: Record<string, string>;
   grammar_paths?: string[];
   query_paths?: string[];
+  grammar_cache?: GrammarCacheOptions;
+  /**
+   * Load every grammar and query up front, failing as soon as the plugin is
+   * created rather than when a code block first uses a broken language.
+   */
+  strict?: boolean;
 };
 
 function extractLanguage(class_name: string[]): string | null {
@@ -101,10 +111,12 @@ function highlightsSinceReset(stack: string[]) {
 export default function rehypeCodeTreeSitter(options?: HighlighterOptions) {
   const grammar_paths = options?.grammar_paths || [];
   const default_query_paths = Array.from(options?.query_paths || []);
-  const highlighter = new highlight.Highlighter(
-    grammar_paths,
-    default_query_paths,
-  );
+  const highlighter = new highlight.Highlighter(grammar_paths, {
+    query_paths: default_query_paths,
+    grammar_cache: options?.grammar_cache,
+    strict: options?.strict,
+    offset_unit: highlight.OffsetUnit.Utf16,
+  });
 
   return function transformer(tree: Element) {
     visit(
@@ -150,17 +162,16 @@ export default function rehypeCodeTreeSitter(options?: HighlighterOptions) {
 
         const [source] = resetContentOffset(child.value);
 
-        // This is not the best way to do this. Ideally there is a way to
-        // specify temporary queries that are loaded for a single call to
-        // highlight without needing to recreate the entire highlighter.
-        let local_highlighter = highlighter;
-        if (query_paths.length > 0) {
-          local_highlighter = new highlight.Highlighter(
-            grammar_paths,
-            default_query_paths.concat(query_paths),
-          );
+        let events: HighlightEvent[];
+        try {
+          events = highlighter.highlight(source, lang, query_paths);
+        } catch (err: any) {
+          // Code blocks in languages without a loaded grammar are left as-is
+          if (err?.code === "InvalidArg") {
+            return;
+          }
+          throw err;
         }
-        const events = local_highlighter.highlight(source, lang);
 
         const children: ElementContent[] = [];
         const highlights_stack: string[] = [];
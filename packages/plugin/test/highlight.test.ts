@@ -122,3 +122,30 @@ test("highlights markdown code with no trailing text", () => {
   const output = processor.processSync(html).value;
   expect(output).matchSnapshot();
 });
+
+test("highlights code with non-ascii text at the right offsets", () => {
+  const html = `
+<html>
+<head></head>
+<body>
+  <pre>
+    <code class="language-javascript">
+      const greeting = "😀 日本語";
+      console.log(greeting);
+    </code>
+  </pre>
+</body>
+</html>`;
+
+  const processor = rehype()
+    .use(rehypeTreeSitter, {
+      grammar_paths: [path.join(__dirname, "../../../fixtures/grammars/")],
+    })
+    .freeze();
+
+  const output = String(processor.processSync(html).value);
+  expect(output).toContain('<span class="string">"😀 日本語"</span>');
+  expect(output).toContain('<span class="punctuation.delimiter">;</span>');
+  expect(output).toContain('<span class="variable.builtin">console</span>');
+  expect(output).toContain('<span class="function.method">log</span>');
+});